@@ -7,27 +7,33 @@ extern crate alloc;
 mod utility;
 mod verify_signature;
 
+use crate::eri::IEri;
 use crate::utility::{EriError::*, *};
 use crate::verify_signature::verify;
 use alloc::string::String;
 use alloc::vec::Vec;
 use alloy_primitives::{Address, FixedBytes};
-use core::convert::Into;
 use stylus_sdk::abi::Bytes;
-use stylus_sdk::{alloy_primitives::U256, evm, prelude::*};
-
-sol_interface! {
-    interface IEri {
-         function createItem(
-            address user,
-            string calldata name,
-            string calldata unique_id,
-            string calldata serial,
-            uint256 date,
-            address owner,
-            string[] memory metadata,
-            string calldata manufacturer_name
-        ) external;
+use stylus_sdk::{alloy_primitives::U256, crypto::keccak, prelude::*};
+
+// the generated createItem binding takes more arguments than clippy allows
+#[allow(clippy::too_many_arguments)]
+mod eri {
+    use super::*;
+
+    sol_interface! {
+        interface IEri {
+             function createItem(
+                address user,
+                string calldata name,
+                string calldata unique_id,
+                string calldata serial,
+                uint256 date,
+                address owner,
+                string[] memory metadata,
+                string calldata manufacturer_name
+            ) external;
+        }
     }
 }
 
@@ -48,6 +54,8 @@ sol_storage! {
     struct Manufacturer {
         string name;
         address manufacturer_address;
+        address royalty_receiver;
+        uint256 royalty_bps;
     }
 }
 
@@ -79,10 +87,13 @@ impl Authenticity {
     pub fn constructor(&mut self, ownership_addr: Address) -> Result<(), EriError> {
         self.ownership.set(ownership_addr);
 
-        evm::log(ContractCreated {
-            contractAddress: self.vm().contract_address(),
-            owner: self.vm().tx_origin(),
-        });
+        log(
+            self.vm(),
+            ContractCreated {
+                contractAddress: self.vm().contract_address(),
+                owner: self.vm().tx_origin(),
+            },
+        );
 
         Ok(())
     }
@@ -90,7 +101,6 @@ impl Authenticity {
     pub fn manufacturer_registers(&mut self, name: String) -> Result<(), EriError> {
         let caller = self.vm().msg_sender();
         self.address_zero_check(caller)?;
-        self.is_registered(caller)?;
 
         if !self
             .manufacturers
@@ -108,7 +118,7 @@ impl Authenticity {
             }));
         }
 
-        if !self.names.get(name.clone()).is_empty() {
+        if !self.names.get(name.clone()).is_zero() {
             return Err(NameNotAvailable(NAME_NOT_AVAILABLE { name: name.clone() }));
         }
 
@@ -118,14 +128,57 @@ impl Authenticity {
 
         self.names.setter(name.clone()).set(caller);
 
-        evm::log(ManufacturerRegistered { //to test, I will have to comment this out
-            manufacturerAddress: caller,
-            manufacturerName: name.clone().parse().unwrap(),
-        });
+        log(
+            self.vm(),
+            ManufacturerRegistered {
+                manufacturerAddress: caller,
+                manufacturerName: keccak(name.as_bytes()),
+            },
+        );
 
         Ok(())
     }
 
+    pub fn set_royalty(&mut self, receiver: Address, royalty_bps: U256) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        self.address_zero_check(caller)?;
+        self.address_zero_check(receiver)?;
+        self.is_registered(caller)?;
+
+        if royalty_bps > U256::from(MAX_ROYALTY_BPS) {
+            return Err(InvalidRoyalty(INVALID_ROYALTY { bps: royalty_bps }));
+        }
+
+        let mut manufacturer = self.manufacturers.setter(caller);
+        manufacturer.royalty_receiver.set(receiver);
+        manufacturer.royalty_bps.set(royalty_bps);
+
+        log(
+            self.vm(),
+            RoyaltySet {
+                manufacturerAddress: caller,
+                receiver,
+                bps: royalty_bps,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn get_royalty(&self, manufacturer: Address) -> (Address, U256) {
+        let manufacturer = self.manufacturers.getter(manufacturer);
+
+        // unregistered manufacturers, or ones that never set a royalty, take nothing
+        if manufacturer.royalty_receiver.get().is_zero() {
+            return (Address::ZERO, U256::ZERO);
+        }
+
+        (
+            manufacturer.royalty_receiver.get(),
+            manufacturer.royalty_bps.get(),
+        )
+    }
+
     fn get_manufacturer_address_by_name(&self, name: String) -> Result<Address, EriError> {
         let address = self.names.get(name);
 
@@ -167,6 +220,7 @@ impl Authenticity {
         Ok(manufacturer)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn verify_signature(
         &self,
         name: String,
@@ -184,6 +238,7 @@ impl Authenticity {
         }
 
         let result = verify(
+            self.vm(),
             name,
             unique_id,
             serial,
@@ -196,6 +251,7 @@ impl Authenticity {
         Ok(result)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn user_claim_ownership(
         &mut self,
         name: String,
@@ -224,23 +280,25 @@ impl Authenticity {
             metadata_hash,
             signature,
         ) {
-            Ok(_) => Ok(ownership
-                .create_item(
-                    self,
-                    caller,
-                    name,
-                    unique_id,
-                    serial,
-                    date,
-                    owner,
-                    metadata,
-                    manufacturer,
-                )
-                .unwrap()),
+            Ok(_) => match ownership.create_item(
+                self,
+                caller,
+                name,
+                unique_id,
+                serial,
+                date,
+                owner,
+                metadata,
+                manufacturer,
+            ) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ClaimFailed(CLAIM_FAILED {})),
+            },
             Err(_) => Err(ClaimFailed(CLAIM_FAILED {})),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn verify_authenticity(
         &self,
         name: String,
@@ -270,7 +328,6 @@ impl Authenticity {
 mod test {
     use super::*;
     use alloc::string::ToString;
-    use stylus_sdk::console;
     use stylus_sdk::testing::*;
 
     #[test]
@@ -280,14 +337,55 @@ mod test {
 
         let _result = contract.manufacturer_registers("SAMSUNG".to_string());
 
-        match contract.get_manufacturer_address_by_name(String::from("SAMSUNG")) {
-            Ok(manufacturer_address) => match contract.get_manufacturer(manufacturer_address) {
-                Ok(manu) => {
-                    assert_eq!(manu.0, String::from("SAMSUNG"));
-                }
-                _ => console!("Error!"),
-            },
-            _ => console!("Error!"),
-        }
+        let manufacturer_address = contract
+            .get_manufacturer_address_by_name(String::from("SAMSUNG"))
+            .ok()
+            .unwrap();
+        let manu = contract
+            .get_manufacturer(manufacturer_address)
+            .ok()
+            .unwrap();
+
+        assert_eq!(manu.0, String::from("SAMSUNG"));
+    }
+
+    #[test]
+    fn test_manufacturer_registers_once_under_a_free_name() {
+        let vm = TestVM::default();
+        let mut contract = Authenticity::from(&vm);
+
+        assert!(contract
+            .manufacturer_registers("SAMSUNG".to_string())
+            .is_ok());
+        assert!(matches!(
+            contract.manufacturer_registers("SAMSUNG-2".to_string()),
+            Err(Registered(_))
+        ));
+
+        vm.set_sender(Address::repeat_byte(0x42));
+        assert!(matches!(
+            contract.manufacturer_registers("SAMSUNG".to_string()),
+            Err(NameNotAvailable(_))
+        ));
+        assert!(contract.manufacturer_registers("APPLE".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_manufacturer_sets_royalty() {
+        let vm = TestVM::default();
+        let mut contract = Authenticity::from(&vm);
+        let receiver = Address::repeat_byte(0x42);
+
+        let _result = contract.manufacturer_registers("SAMSUNG".to_string());
+
+        assert!(matches!(
+            contract.set_royalty(receiver, U256::from(MAX_ROYALTY_BPS + 1)),
+            Err(InvalidRoyalty(_))
+        ));
+        assert!(contract.set_royalty(receiver, U256::from(250)).is_ok());
+        assert_eq!(
+            contract.get_royalty(vm.msg_sender()),
+            (receiver, U256::from(250))
+        );
     }
 }
@@ -6,5 +6,5 @@ pub extern "C" fn main() {}
 
 #[cfg(feature = "export-abi")]
 fn main() {
-    Authenticity::print_from_args();
+    authenticity::print_from_args();
 }
@@ -1,7 +1,6 @@
 use alloy_sol_types::sol;
 use stylus_sdk::prelude::SolidityError;
 
-
 sol! {
 
     error ADDRESS_ZERO(address zero);
@@ -13,15 +12,16 @@ sol! {
     error INVALID_SIGNATURE();
     error EC_RECOVER_CALL_ERROR();
     error DOES_NOT_EXIST();
+    error INVALID_ROYALTY(uint256 bps);
 
 
     event ManufacturerRegistered(address indexed manufacturerAddress, string indexed manufacturerName);
     event ContractCreated(address indexed contractAddress, address indexed owner);
+    event RoyaltySet(address indexed manufacturerAddress, address indexed receiver, uint256 bps);
 }
 
 #[derive(SolidityError)]
 pub enum EriError {
-
     AddressZero(ADDRESS_ZERO),
     NotRegistered(NOT_REGISTERED),
     Registered(ALREADY_REGISTERED),
@@ -30,6 +30,9 @@ pub enum EriError {
     ClaimFailed(CLAIM_FAILED),
     InvalidSignature(INVALID_SIGNATURE),
     ECRecoverError(EC_RECOVER_CALL_ERROR),
-    DoesNotExist(DOES_NOT_EXIST)
+    DoesNotExist(DOES_NOT_EXIST),
+    InvalidRoyalty(INVALID_ROYALTY),
 }
 
+/// Highest royalty a manufacturer may take from a resale, in basis points (10%).
+pub const MAX_ROYALTY_BPS: u64 = 1_000;
@@ -13,11 +13,10 @@ use alloy_sol_types::{
 use stylus_sdk::{
     abi::Bytes,
     alloy_primitives::{address, Address, U256},
-    call::{self, Call},
     crypto::keccak,
     prelude::*,
+    stylus_core::calls::context::Call,
 };
-use stylus_sdk::call::static_call;
 // impl VerifySignature {
 /* 1. Unlock MetaMask account
 ethereum.enable()
@@ -47,11 +46,11 @@ fn get_message_hash(
         unique_id.as_bytes(),
         serial.as_bytes(),
         &date.to_be_bytes_vec(),
-        &owner.to_vec(),
-        &metadata_hash.to_vec(),
+        owner.as_slice(),
+        metadata_hash.as_slice(),
     ]
     .concat();
-    keccak(message_data).into()
+    keccak(message_data)
 }
 
 /* 3. Sign message hash
@@ -66,10 +65,10 @@ Signature will be different for different accounts
 0x993dab3dd91f5c6dc28e17439be475478f5635c92a56e17e82349d3fb2f166196f466c0b4e0c146f285204f0dcb13e5ae67bc33f4b888ec32dfe0a063e8f3f781b
 */
 fn get_eth_signed_message_hash(message_hash: FixedBytes<32>) -> FixedBytes<32> {
-    const SIGNED_MESSAGE_HEAD: &'static str = "\x19Ethereum Signed Message:\n32";
+    const SIGNED_MESSAGE_HEAD: &str = "\x19Ethereum Signed Message:\n32";
 
-    let message_to_be_decoded = [SIGNED_MESSAGE_HEAD.as_bytes(), &message_hash.to_vec()].concat();
-    keccak(message_to_be_decoded).into()
+    let message_to_be_decoded = [SIGNED_MESSAGE_HEAD.as_bytes(), message_hash.as_slice()].concat();
+    keccak(message_to_be_decoded)
 }
 
 /* 4. Verify signature
@@ -81,7 +80,9 @@ nonce = 1
 signature =
     0x993dab3dd91f5c6dc28e17439be475478f5635c92a56e17e82349d3fb2f166196f466c0b4e0c146f285204f0dcb13e5ae67bc33f4b888ec32dfe0a063e8f3f781b
 */
+#[allow(clippy::too_many_arguments)]
 pub fn verify(
+    vm: &dyn Host,
     // expected_signer: Address,
     name: String,
     unique_id: String,
@@ -95,22 +96,24 @@ pub fn verify(
 
     let eth_signed_message_hash = get_eth_signed_message_hash(message_hash);
 
-    match recover_signer(eth_signed_message_hash, signature) {
+    match recover_signer(vm, eth_signed_message_hash, signature) {
         Ok(recovered_signer) => Ok(recovered_signer == owner),
-        Err(err) => Err(InvalidSignature(INVALID_SIGNATURE {})),
+        Err(_) => Err(InvalidSignature(INVALID_SIGNATURE {})),
     }
 }
 
 fn recover_signer(
+    vm: &dyn Host,
     eth_signed_message_hash: FixedBytes<32>,
     signature: Bytes,
 ) -> Result<Address, EriError> {
     let (r, s, v) = split_signature(signature);
-    ec_recover_call(eth_signed_message_hash, v, r, s)
+    ec_recover_call(vm, eth_signed_message_hash, v, r, s)
 }
 
 /// Invoke the ECRECOVER precompile.
 fn ec_recover_call(
+    vm: &dyn Host,
     hash: FixedBytes<32>,
     v: u8,
     r: FixedBytes<32>,
@@ -128,7 +131,7 @@ fn ec_recover_call(
     let data = (hash, v, r, s);
     let encoded_data = ECRECOVERType::abi_encode(&data);
 
-    match call::static_call(Call::new(), EC_RECOVER, &encoded_data) {
+    match vm.static_call(&Call::new(), EC_RECOVER, &encoded_data) {
         Ok(result) => Ok(SOLAddress::abi_decode(&result, false).unwrap()),
         Err(_) => Err(ECRecoverError(EC_RECOVER_CALL_ERROR {})),
     }
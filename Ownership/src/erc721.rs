@@ -0,0 +1,103 @@
+// ERC-721 and ERC-165 plumbing so items show up in standard wallets and marketplaces.
+
+use crate::utility::{EriError::*, *};
+use alloc::vec::Vec;
+use alloy_primitives::{Address, FixedBytes, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use stylus_sdk::prelude::*;
+use stylus_sdk::stylus_core::calls::context::Call;
+
+sol! {
+    interface IERC721Receiver {
+        function onERC721Received(address operator, address from, uint256 tokenId, bytes data) external returns (bytes4);
+    }
+
+    interface IERC165 {
+        function supportsInterface(bytes4 interfaceId) external view returns (bool);
+    }
+
+    interface IERC721 {
+        function balanceOf(address owner) external view returns (uint256);
+        function ownerOf(uint256 tokenId) external view returns (address);
+        function safeTransferFrom(address from, address to, uint256 tokenId, bytes data) external;
+        function safeTransferFrom(address from, address to, uint256 tokenId) external;
+        function transferFrom(address from, address to, uint256 tokenId) external;
+        function approve(address approved, uint256 tokenId) external;
+        function setApprovalForAll(address operator, bool approved) external;
+        function getApproved(uint256 tokenId) external view returns (address);
+        function isApprovedForAll(address owner, address operator) external view returns (bool);
+    }
+
+    interface IERC721Metadata {
+        function name() external view returns (string);
+        function symbol() external view returns (string);
+        function tokenURI(uint256 tokenId) external view returns (string);
+    }
+
+    interface IERC2981 {
+        function royaltyInfo(uint256 tokenId, uint256 salePrice) external view returns (address, uint256);
+    }
+
+    interface IERC5192 {
+        function locked(uint256 tokenId) external view returns (bool);
+    }
+
+    interface IERC4907 {
+        function setUser(uint256 tokenId, address user, uint64 expires) external;
+        function userOf(uint256 tokenId) external view returns (address);
+        function userExpires(uint256 tokenId) external view returns (uint256);
+    }
+}
+
+/// Function selectors of every interface reported through `supportsInterface`. An interface is
+/// only listed here once all of its functions are implemented.
+pub const SUPPORTED_INTERFACES: [&[[u8; 4]]; 6] = [
+    IERC165::IERC165Calls::SELECTORS,
+    IERC721::IERC721Calls::SELECTORS,
+    IERC721Metadata::IERC721MetadataCalls::SELECTORS,
+    IERC2981::IERC2981Calls::SELECTORS,
+    IERC5192::IERC5192Calls::SELECTORS,
+    IERC4907::IERC4907Calls::SELECTORS,
+];
+
+/// ERC-165 interface id: the XOR of the interface's function selectors.
+pub fn interface_id(selectors: &[[u8; 4]]) -> FixedBytes<4> {
+    let id = selectors
+        .iter()
+        .fold(0u32, |id, selector| id ^ u32::from_be_bytes(*selector));
+
+    FixedBytes(id.to_be_bytes())
+}
+
+pub fn supports_interface(id: FixedBytes<4>) -> bool {
+    SUPPORTED_INTERFACES
+        .iter()
+        .any(|selectors| interface_id(selectors) == id)
+}
+
+/// Makes sure a contract receiving an item through `safeTransferFrom` knows how to hold it.
+pub fn check_on_erc721_received(
+    vm: &dyn Host,
+    operator: Address,
+    from: Address,
+    to: Address,
+    token_id: U256,
+    data: Vec<u8>,
+) -> Result<(), EriError> {
+    let call = IERC721Receiver::onERC721ReceivedCall {
+        operator,
+        from,
+        tokenId: token_id,
+        data: data.into(),
+    };
+
+    match vm.call(&Call::new(), to, &call.abi_encode()) {
+        Ok(result)
+            if FixedBytes::<4>::abi_decode(&result, false).ok()
+                == Some(FixedBytes(IERC721Receiver::onERC721ReceivedCall::SELECTOR)) =>
+        {
+            Ok(())
+        }
+        _ => Err(UnsafeRecipient(UNSAFE_RECIPIENT { to })),
+    }
+}
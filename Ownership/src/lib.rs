@@ -3,18 +3,26 @@
 
 #[macro_use]
 extern crate alloc;
+mod erc721;
+mod royalty;
+mod token;
 mod utility;
 
+use crate::erc721::check_on_erc721_received;
+use crate::royalty::get_royalty;
+use crate::token::{safe_transfer, safe_transfer_from};
 use crate::utility::{EriError::*, *};
 use alloc::string::String;
 use alloc::vec::Vec;
-use alloy_primitives::{Address, FixedBytes};
+use alloy_primitives::{aliases::U8, Address, FixedBytes};
+use stylus_sdk::abi::Bytes;
 // use alloy_sol_types::{
 //     sol_data::{Address as SOLAddress, String as SolString, *},
 //     SolType,
 //     SolValue
 // };
 use alloy_sol_types::SolValue;
+use stylus_sdk::storage::StorageGuard;
 use stylus_sdk::{alloy_primitives::U256, crypto::keccak, prelude::*};
 
 sol_storage! {
@@ -29,22 +37,103 @@ sol_storage! {
 
         mapping(address => string) usernames;
 
-        mapping(string => address) owners;
+        mapping(bytes32 => Item) items;
 
-        mapping(address => mapping(string => Item)) owned_items;
+        mapping(string => bytes32) item_keys;
 
-        mapping(address => Item[]) my_items;
+        mapping(string => bool) ambiguous_ids;
+
+        mapping(address => bytes32[]) my_items;
+
+        mapping(address => mapping(bytes32 => uint256)) my_item_indexes;
 
         mapping(bytes32 => address) temp;
 
-        mapping(bytes32 => mapping(address => Item)) temp_owners;
+        mapping(bytes32 => TransferOffer) offers;
+
+        mapping(bytes32 => bytes32) pending_codes;
+
+        mapping(bytes32 => mapping(address => ClaimCommit)) claim_commits;
+
+        mapping(address => uint256) proceeds;
+
+        mapping(address => mapping(address => uint256)) token_proceeds;
+
+        mapping(address => uint256) balances;
+
+        string base_uri;
+
+        mapping(bytes32 => bool) locked_items;
+
+        mapping(address => mapping(string => bool)) locked_models;
+
+        mapping(bytes32 => ProvenanceRecord[]) provenance;
+
+        mapping(address => bytes32[]) manufacturer_items;
+
+        mapping(address => ManufacturerStats) manufacturer_stats;
+
+        mapping(bytes32 => Warranty) warranties;
+
+        mapping(bytes32 => bool) decommission_requests;
+
+        mapping(bytes32 => address) token_approvals;
+
+        mapping(bytes32 => ItemUser) item_users;
+
+        mapping(address => mapping(address => bool)) operator_approvals;
+        mapping(address => uint256) operator_counts;
+
+        mapping(address => mapping(address => bool)) service_centres;
+
+        mapping(bytes32 => ServiceRecord[]) service_records;
+
+        mapping(address => Organisation) organisations;
+
+        mapping(address => address[]) org_members;
+
+        mapping(address => mapping(address => uint256)) org_member_indexes;
+
+        mapping(address => mapping(address => uint8)) org_roles;
+
+        mapping(address => address[]) member_orgs;
+
+        mapping(address => mapping(address => uint256)) member_org_indexes;
+
+        mapping(address => Inheritance) inheritances;
+
+        mapping(address => uint256) last_active;
+
+        mapping(address => address) inherited_by;
+
+        mapping(address => address[]) guardians;
+
+        mapping(address => mapping(address => bool)) is_guardian;
+
+        mapping(address => uint256) guardian_thresholds;
+
+        mapping(address => RecoveryRequest) recoveries;
+
+        mapping(address => mapping(address => RecoveryVote)) recovery_approvals;
+
+        mapping(address => mapping(uint256 => mapping(address => uint256))) recovery_tallies;
+
+        mapping(address => address) recovered_to;
+
+        bool entered;
+
+        uint256 transfer_duration;
     }
 
+    #[derive(Erase)]
     struct UserProfile {
         address user_address;
         string username;
         bool registered;
         uint256 registered_at;
+        string avatar_uri;
+        bytes32 contact_hash;
+        string preferred_language;
     }
 
     #[derive(Erase)]
@@ -56,6 +145,86 @@ sol_storage! {
         address owner;
         string manufacturer;
         string[] metadata;
+        address manufacturer_address;
+        bool stolen;
+        bool decommissioned;
+    }
+
+    #[derive(Erase)]
+    struct TransferOffer {
+        address creator;
+        bytes32 item_key;
+        uint256 expires_at;
+        bytes32 secret_hash;
+        uint256 price;
+        address token;
+    }
+
+    struct ProvenanceRecord {
+        address owner;
+        uint256 acquired_at;
+        uint8 kind;
+    }
+
+    struct ManufacturerStats {
+        uint256 total_claimed;
+        uint256 total_transferred;
+    }
+
+    struct Warranty {
+        uint256 start;
+        uint256 duration;
+        string terms_uri;
+        bool transferable;
+        address holder;
+    }
+
+    struct ServiceRecord {
+        address centre;
+        uint256 timestamp;
+        string service_type;
+        bytes32 description_hash;
+        string uri;
+    }
+
+    #[derive(Erase)]
+    struct ItemUser {
+        address user;
+        uint256 expires;
+    }
+
+    struct Organisation {
+        string name;
+        uint256 admin_count;
+        uint256 created_at;
+    }
+
+    #[derive(Erase)]
+    struct Inheritance {
+        address beneficiary;
+        uint256 inactivity_period;
+        uint256 claim_started_at;
+    }
+
+    struct RecoveryRequest {
+        address new_address;
+        uint256 approvals;
+        uint256 ready_at;
+        uint256 nonce;
+        uint256 votes;
+    }
+
+    struct RecoveryVote {
+        address new_address;
+        uint256 nonce;
+    }
+
+    #[derive(Erase)]
+    struct ClaimCommit {
+        bytes32 commitment;
+        uint256 block_number;
+        uint256 deposit;
+        address token;
     }
 }
 
@@ -87,242 +256,147 @@ impl Ownership {
 
         Ok(())
     }
-    fn is_item_owner(&self, item_id: String) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
-
-        if caller != self.owners.get(item_id) {
-            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+    // resolves the public string id of a claimed item to the key it is stored under. Once
+    // a second manufacturer issues the same id it no longer names one item, and callers
+    // have to use the manufacturer-qualified entrypoints instead
+    fn key_of(&self, item_id: String) -> Result<FixedBytes<32>, EriError> {
+        if self.ambiguous_ids.get(item_id.clone()) {
+            return Err(AmbiguousItemId(AMBIGUOUS_ITEM_ID { itemId: item_id }));
         }
 
-        Ok(())
-    }
-}
-
-#[public]
-impl Ownership {
-    #[constructor]
-    fn constructor(&mut self, owner: Address) -> Result<(), EriError> {
-        self.address_zero_check(owner)?;
-
-        self.owner.set(owner);
-
-        stylus_sdk::evm::log(ContractCreated {
-            contractAddress: self.vm().contract_address(),
-            owner,
-        });
+        let key = self.item_keys.get(item_id.clone());
 
-        Ok(())
-    }
-    fn set_authenticity(&mut self, authenticity_address: Address) -> Result<(), EriError> {
-        self.is_authenticity_set()?;
-        self.address_zero_check(authenticity_address)?;
-        let caller = self.vm().msg_sender();
-        //ONLY OWNER
-        if caller != self.owner.get() {
-            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        if key.is_zero() {
+            return Err(ItemDoesNotExist(ITEM_DOESNT_EXIST { itemId: item_id }));
         }
 
-        self.owner.set(authenticity_address);
-
-        stylus_sdk::evm::log(AuthenticitySet {
-            authenticityAddress: authenticity_address,
-        });
-
-        Ok(())
+        Ok(key)
     }
-    fn user_registers(&mut self, username: String) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
-
-        self.is_authenticity_set()?;
-        self.address_zero_check(caller)?;
 
-        let time = self.vm().block_timestamp();
+    fn qualified_key_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<FixedBytes<32>, EriError> {
+        let key = item_key(manufacturer, &unique_id);
 
-        if username.len() < 3 {
-            return Err(BadUsername(USERNAME_MUST_BE_AT_LEAST_3_LETTERS {}));
+        // a decommissioned item stays addressable so its record and history remain readable
+        if self.item_owner(key).is_zero() && !self.items.getter(key).decommissioned.get() {
+            return Err(ItemDoesNotExist(ITEM_DOESNT_EXIST { itemId: unique_id }));
         }
 
-        let mut user = self.users.setter(username.clone());
+        Ok(key)
+    }
 
-        if user.registered.get() {
-            return Err(NotAvailable(NAME_NOT_AVAILABLE {
-                username: username.clone(),
-            }));
-        }
+    // the current owner of an item that can still be acted on
+    fn live_owner(&self, key: FixedBytes<32>) -> Result<Address, EriError> {
+        let owner = self.item_owner(key);
 
-        let mut fetched_username = self.usernames.setter(caller);
-        if !fetched_username.get_string().is_empty() {
-            return Err(Registered(ALREADY_REGISTERED { caller }));
+        if owner.is_zero() {
+            return Err(ItemDoesNotExist(ITEM_DOESNT_EXIST {
+                itemId: self.items.getter(key).item_id.get_string(),
+            }));
         }
 
-        user.user_address.set(caller);
-        user.username.set_str(username.clone());
-        user.registered.set(true);
-        user.registered_at.set(U256::from(time));
-
-        fetched_username.set_str(username.clone());
-
-        stylus_sdk::evm::log(UserRegistered {
-            userAddress: caller,
-            username: username.parse().unwrap(),
-        });
+        Ok(owner)
+    }
 
-        Ok(())
+    fn item_owner(&self, key: FixedBytes<32>) -> Address {
+        self.items.getter(key).owner.get()
     }
-    fn get_user(&self, user_address: Address) -> Result<(Address, String, bool, U256), EriError> {
-        self.is_authenticity_set()?;
-        let username = self.usernames.get(user_address);
-        let user = self.users.get(username.get_string());
 
-        if user.user_address.get().is_zero() {
-            return Err(NotExist(USER_DOES_NOT_EXIST { user: user_address }));
-        }
+    fn is_org_admin(&self, organisation: Address, member: Address) -> bool {
+        self.org_roles.getter(organisation).get(member).to::<u8>() == ROLE_ADMIN
+    }
 
-        Ok((
-            user.user_address.get(), //user address
-            user.username.get_string(), //user name
-            user.registered.get(), //is_registered
-            user.registered_at.get(), //reg time
-        ))
+    // an organisation's items are managed by its admins. A recovered key acts for nothing,
+    // its leftover items wait for migrate_recovered_items
+    fn acts_for(&self, owner: Address, caller: Address) -> bool {
+        !self.is_recovered(owner) && (caller == owner || self.is_org_admin(owner, caller))
     }
 
-    fn create_item(
-        &mut self,
-        user: Address,
-        name: String,
-        unique_id: String,
-        serial: String,
-        date: U256,
-        owner: Address,
-        metadata: Vec<String>,
-        manufacturer_name: String,
-    ) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
+    fn is_recovered(&self, account: Address) -> bool {
+        !self.recovered_to.get(account).is_zero()
+    }
 
-        self.is_authenticity_set()?;
-        self.address_zero_check(caller)?;
-        self.address_zero_check(user)?;
-        self.is_registered(user)?;
+    // follows a chain of recoveries to the address that now holds the account
+    fn recovered_account(&self, account: Address) -> Address {
+        let mut current = account;
 
-        if caller != self.authenticity.get() {
-            //making sure only Authenticity can call this function
-            return Err(Unauthorized(UNAUTHORIZED { caller }));
-        }
-        if owner.is_zero() {
-            return Err(AddressZero(ADDRESS_ZERO { zero: owner }));
+        while self.is_recovered(current) {
+            current = self.recovered_to.get(current);
         }
 
-        if !self.owners.get(unique_id.clone()).is_zero() {
-            return Err(AlreadyClaimed(ITEM_CLAIMED_ALREADY {
-                itemId: unique_id.clone(),
+        current
+    }
+
+    // whatever was arranged for a recovered key moved with the account
+    fn is_not_recovered(&self, account: Address) -> Result<(), EriError> {
+        if self.is_recovered(account) {
+            return Err(WasRecovered(ACCOUNT_WAS_RECOVERED {
+                account,
+                newAddress: self.recovered_account(account),
             }));
         }
 
-        //======== GENERAL ITEMS ==========
-        let mut user_item = self.owned_items.setter(user);
-        let mut item = user_item.setter(unique_id.clone());
-
-        set_item(
-            &mut item,
-            user,
-            name.clone(),
-            unique_id.clone(),
-            serial.clone(),
-            date,
-            manufacturer_name.clone(),
-        );
-
-        //======== PERSONAL ITEM =============
-        let mut my_items_vec = self.my_items.setter(user);
-        let mut new_item = my_items_vec.grow();
-
-        set_item(
-            &mut new_item,
-            user,
-            name,
-            unique_id.clone(),
-            serial,
-            date,
-            manufacturer_name,
-        );
+        Ok(())
+    }
 
-        for meta in metadata {
-            let mut guard = item.metadata.grow();
-            guard.set_str(meta.clone());
+    fn is_item_owner(&self, key: FixedBytes<32>) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
 
-            // Adds a new StorageString slot and returns a guard
-            let mut guard = new_item.metadata.grow();
-            guard.set_str(meta);
+        if !self.acts_for(self.item_owner(key), caller) {
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
         }
 
-        // item id to a user address
-        self.owners.setter(unique_id.clone()).set(user);
-
-        stylus_sdk::evm::log(ItemCreated {
-            itemId: unique_id.parse().unwrap(),
-            owner: user,
-        });
-
         Ok(())
     }
 
-    fn get_all_my_items(
-        &self,
-    ) -> Result<Vec<(String, String, String, U256, Address, String, Vec<String>)>, EriError> {
-        self.is_authenticity_set()?;
-
+    // returns the owner the caller may act for: the owner itself (or its admins for an
+    // organisation), the item's approved address or one of the owner's operators. Codes are issued in the owner's name so sale proceeds
+    // reach them even when an operator lists the item
+    fn is_owner_or_approved(&self, key: FixedBytes<32>) -> Result<Address, EriError> {
         let caller = self.vm().msg_sender();
+        let owner = self.item_owner(key);
 
-        if self
-            .users
-            .get(self.usernames.get(caller).get_string())
-            .user_address
-            .get()
-            .is_zero()
+        if owner.is_zero()
+            || self.is_recovered(owner)
+            || (!self.acts_for(owner, caller)
+                && caller != self.token_approvals.get(key)
+                && !self.operator_approvals.getter(owner).get(caller))
         {
-            return Err(NotExist(USER_DOES_NOT_EXIST { user: caller }));
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
         }
 
-        let item_list = self.my_items.get(caller);
-
-        let mut new_list = Vec::new();
-
-        for i in 0..item_list.len() {
-            let item_guard = self.owned_items.get(caller);
-            let owned_item = item_guard.get(item_list.get(i).unwrap().item_id.get_string());
-
-            if !owned_item.owner.get().is_zero() {
-                let mut meta = Vec::new();
-
-                for i in 0..owned_item.metadata.len() {
-                    meta.push(owned_item.metadata.get(i).unwrap().get_string())
-                }
-
-                new_list.push(item_tuple(&owned_item, meta))
-            }
-        }
-        Ok(new_list)
+        Ok(owner)
     }
 
-    fn generate_change_of_ownership_code(
+    fn issue_offer(
         &mut self,
-        item_id: String,
+        caller: Address,
+        key: FixedBytes<32>,
         temp_owner: Address,
+        secret_hash: FixedBytes<32>,
+        token: Address,
+        price: U256,
     ) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
+        self.is_transferable(key)?;
+        self.touch();
 
-        self.address_zero_check(caller)?;
-        self.address_zero_check(temp_owner)?;
-        self.is_authenticity_set()?;
-        self.is_registered(caller)?;
-        self.is_item_owner(item_id.clone())?;
+        let now = U256::from(self.vm().block_timestamp());
 
-        if caller == temp_owner {
-            return Err(CannotGenerate(CANNOT_GENERATE_CODE_FOR_YOURSELF { caller }));
+        // an expired code no longer blocks the item, so it is cleared before issuing a new one
+        let pending_code = self.pending_codes.get(key);
+        if !self.offers.get(pending_code).creator.get().is_zero() {
+            if self.offers.get(pending_code).expires_at.get() >= now {
+                return Err(NotClaimed(ITEM_NOT_CLAIMED_YET {}));
+            }
+            self.clear_offer(pending_code);
         }
 
-        let mut user_item = self.owned_items.setter(caller);
-        let item = user_item.setter(item_id);
+        let expires_at = now + self.transfer_duration.get();
+
+        let item = self.items.getter(key);
 
         let mut meta = Vec::new();
 
@@ -330,208 +404,5038 @@ impl Ownership {
             meta.push(item.metadata.get(i).unwrap().get_string())
         }
 
-        let inner_item = (
-            item.name.get_string(),
-            item.item_id.get_string(),
-            item.serial.get_string(),
-            item.date.get(),
-            item.owner.get(),
-            item.manufacturer.get_string(),
-            meta,
-        );
-        type InnerItemTuple = (String, String, String, U256, Address, String, Vec<String>);
-        let encoded = InnerItemTuple::abi_encode_sequence(&inner_item);
-        let item_hash: FixedBytes<32> = keccak(encoded).into();
-
-        if !self.temp.get(item_hash).is_zero() {
-            return Err(NotClaimed(ITEM_NOT_CLAIMED_YET {}));
-        }
+        // binding the recipient, expiry, secret and terms into the code makes every offer unique
+        let encoded = (
+            item_tuple(&item, meta),
+            temp_owner,
+            expires_at,
+            secret_hash,
+            token,
+            price,
+        )
+            .abi_encode_sequence();
+        let item_hash: FixedBytes<32> = keccak(encoded);
 
         self.temp.setter(item_hash).set(temp_owner);
 
-        let mut owner_guard = self.temp_owners.setter(item_hash);
-        let mut t_owner = owner_guard.setter(temp_owner);
+        let mut offer = self.offers.setter(item_hash);
+        offer.creator.set(caller);
+        offer.item_key.set(key);
+        offer.expires_at.set(expires_at);
+        offer.secret_hash.set(secret_hash);
+        offer.price.set(price);
+        offer.token.set(token);
 
-        set_item(
-            &mut t_owner,
-            item.owner.get(),
-            item.name.get_string(),
-            item.item_id.get_string(),
-            item.serial.get_string(),
-            item.date.get(),
-            item.manufacturer.get_string(),
+        self.pending_codes.setter(key).set(item_hash);
+
+        log(
+            self.vm(),
+            OwnershipCode {
+                ownershipCode: item_hash,
+                tempOwner: temp_owner,
+                expiresAt: expires_at,
+            },
         );
 
-        stylus_sdk::evm::log(OwnershipCode {
-            ownershipCode: item_hash,
-            tempOwner: temp_owner,
-        });
+        if !price.is_zero() {
+            log(
+                self.vm(),
+                SaleListed {
+                    ownershipCode: item_hash,
+                    token,
+                    price,
+                },
+            );
+        }
 
         Ok(())
     }
 
-    fn new_owner_claim_ownership(&mut self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
+    // takes the price of an offer from the payer and returns the amount received
+    fn collect_payment(
+        &mut self,
+        item_hash: FixedBytes<32>,
+        payer: Address,
+    ) -> Result<U256, EriError> {
+        let price = self.offers.get(item_hash).price.get();
+        let token = self.offers.get(item_hash).token.get();
+        let value = self.vm().msg_value();
 
-        self.is_authenticity_set()?;
-        self.address_zero_check(caller)?;
-        self.is_registered(caller)?;
+        if token.is_zero() {
+            if value != price {
+                return Err(IncorrectPayment(INCORRECT_PAYMENT {
+                    expected: price,
+                    sent: value,
+                }));
+            }
+
+            return Ok(price);
+        }
 
-        let temp_owner = self.temp.get(item_hash);
+        if !value.is_zero() {
+            return Err(IncorrectPayment(INCORRECT_PAYMENT {
+                expected: U256::ZERO,
+                sent: value,
+            }));
+        }
 
-        let mut user_item = self.temp_owners.setter(item_hash);
-        let mut item = user_item.setter(caller);
+        safe_transfer_from(self.vm(), token, payer, price)
+    }
 
-        let old_owner = item.owner.get();
+    fn credit(&mut self, token: Address, account: Address, amount: U256) {
+        // royalties and other late payments to a recovered account reach its new address
+        let account = self.recovered_account(account);
 
-        if caller != caller || old_owner.is_zero() {
-            return Err(Unauthorized(UNAUTHORIZED { caller }));
+        if token.is_zero() {
+            let mut balance = self.proceeds.setter(account);
+            let current = balance.get();
+            balance.set(current + amount);
+        } else {
+            let mut token_balances = self.token_proceeds.setter(token);
+            let mut balance = token_balances.setter(account);
+            let current = balance.get();
+            balance.set(current + amount);
         }
+    }
 
-        item.owner.set(caller); // set the new owner for the item
+    // asks Authenticity for the manufacturer's royalty terms and applies them to a sale price
+    fn royalty_for(
+        &self,
+        manufacturer: Address,
+        sale_price: U256,
+    ) -> Result<(Address, U256), EriError> {
+        let (receiver, bps) = get_royalty(self.vm(), self.authenticity.get(), manufacturer)?;
 
-        //remove the item from old owner's item list
-        let mut old_owner_item_list = self.my_items.setter(old_owner);
-        for i in 0..old_owner_item_list.len() {
-            let mut guard = old_owner_item_list.setter(i).unwrap();
-            if guard.item_id.get_string() == item.item_id.get_string() {
-                guard.erase();
-                break;
-            }
+        if receiver.is_zero() {
+            return Ok((Address::ZERO, U256::ZERO));
         }
 
-        let item_id = item.item_id.get_string();
+        Ok((receiver, sale_price * bps / U256::from(BPS_DENOMINATOR)))
+    }
 
-        self.owned_items.setter(old_owner).delete(item_id.clone()); //delete the item from the old owner mapping
+    fn lock(&mut self) -> Result<(), EriError> {
+        if self.entered.get() {
+            return Err(Reentrancy(REENTRANT_CALL {}));
+        }
 
-        let mut item_guard = self.owned_items.setter(caller);
-        let mut save_item = item_guard.setter(item_id.clone());
+        self.entered.set(true);
 
-        set_item(
-            &mut save_item,
-            item.owner.get(),
-            item.name.get_string(),
-            item.item_id.get_string(),
-            item.serial.get_string(),
-            item.date.get(),
-            item.manufacturer.get_string(),
-        );
+        Ok(())
+    }
 
-        self.owners.setter(item_id).set(caller);
+    fn unlock(&mut self) {
+        self.entered.set(false);
+    }
 
-        let mut item_list = self.my_items.setter(caller);
+    fn is_locked(&self, key: FixedBytes<32>) -> bool {
+        let item = self.items.getter(key);
 
-        let mut guard = item_list.grow();
+        self.locked_items.get(key)
+            || self
+                .locked_models
+                .getter(item.manufacturer_address.get())
+                .get(item.name.get_string())
+    }
 
-        set_item(
-            &mut guard,
-            item.owner.get(),
-            item.name.get_string(),
-            item.item_id.get_string(),
-            item.serial.get_string(),
-            item.date.get(),
-            item.manufacturer.get_string(),
-        );
+    fn is_transferable(&self, key: FixedBytes<32>) -> Result<(), EriError> {
+        if self.items.getter(key).stolen.get() {
+            return Err(ItemStolen(ITEM_REPORTED_STOLEN {
+                itemId: self.items.getter(key).item_id.get_string(),
+            }));
+        }
 
-        self.temp_owners.setter(item_hash).delete(caller);
-        self.temp.delete(item_hash);
+        if self.is_locked(key) {
+            return Err(ItemLocked(ITEM_LOCKED {
+                itemId: self.items.getter(key).item_id.get_string(),
+            }));
+        }
 
-        stylus_sdk::evm::log(OwnershipClaimed {
-            newOwner: caller,
-            oldOwner: old_owner,
-        });
+        Ok(())
+    }
+
+    fn is_item_manufacturer(&self, key: FixedBytes<32>) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        if caller != self.items.getter(key).manufacturer_address.get() {
+            return Err(Unauthorized(UNAUTHORIZED { caller }));
+        }
 
         Ok(())
     }
-    fn get_temp_owner(&self, item_hash: FixedBytes<32>) -> Result<Address, EriError> {
-        self.is_authenticity_set()?;
 
-        Ok(self.temp.get(item_hash))
+    fn is_offer_claimable(&self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
+        let offer = self.offers.get(item_hash);
+
+        // codes the lost key issued die with it, even before the item is migrated
+        if offer.creator.get().is_zero() || self.is_recovered(offer.creator.get()) {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        if U256::from(self.vm().block_timestamp()) > offer.expires_at.get() {
+            return Err(CodeExpired(OWNERSHIP_CODE_EXPIRED {
+                itemHash: item_hash,
+            }));
+        }
+
+        // a lock applied after the code was issued still stops the claim
+        self.is_transferable(offer.item_key.get())
     }
 
-    fn owner_revoke_code(&mut self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
-        let caller = self.vm().msg_sender();
+    fn complete_transfer(
+        &mut self,
+        item_hash: FixedBytes<32>,
+        new_owner: Address,
+        paid: U256,
+    ) -> Result<(), EriError> {
+        self.touch();
 
+        let creator = self.offers.get(item_hash).creator.get();
+        let key = self.offers.get(item_hash).item_key.get();
+        let token = self.offers.get(item_hash).token.get();
+        let kind = if self.offers.get(item_hash).price.get().is_zero() {
+            PROVENANCE_GIFT
+        } else {
+            PROVENANCE_SALE
+        };
+
+        let old_owner = self.item_owner(key);
+        let manufacturer = self.items.getter(key).manufacturer_address.get();
+
+        // only the owner who issued the code can be the one giving the item up
+        if old_owner.is_zero() || old_owner != creator {
+            return Err(Unauthorized(UNAUTHORIZED { caller: new_owner }));
+        }
+
+        self.move_item(key, old_owner, new_owner, kind);
+
+        self.clear_offer(item_hash);
+
+        // the sale price stays in the contract until the seller and manufacturer withdraw it
+        if !paid.is_zero() {
+            let (receiver, royalty) = self.royalty_for(manufacturer, paid)?;
+
+            if !royalty.is_zero() {
+                self.credit(token, receiver, royalty);
+
+                log(
+                    self.vm(),
+                    RoyaltyPaid {
+                        receiver,
+                        token,
+                        amount: royalty,
+                    },
+                );
+            }
+
+            self.credit(token, creator, paid - royalty);
+        }
+
+        log(
+            self.vm(),
+            OwnershipClaimed {
+                newOwner: new_owner,
+                oldOwner: old_owner,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_item(&mut self, key: FixedBytes<32>, from: Address, to: Address, kind: u8) {
+        // an approval, lending or take-back request is granted by one owner and never
+        // carries over to the next
+        self.token_approvals.delete(key);
+        self.clear_user(key);
+        self.decommission_requests.delete(key);
+
+        self.remove_from_my_items(from, key);
+
+        self.items.setter(key).owner.set(to);
+
+        self.add_to_my_items(to, key);
+
+        let from_balance = self.balances.get(from);
+        self.balances.setter(from).set(from_balance - U256::from(1));
+        let to_balance = self.balances.get(to);
+        self.balances.setter(to).set(to_balance + U256::from(1));
+
+        self.record_provenance(key, to, kind);
+
+        // a non-transferable warranty stays with the buyer who claimed it, except when the
+        // same person moves to a recovered address
+        let warranty = self.warranties.get(key);
+        if !warranty.duration.get().is_zero()
+            && (warranty.transferable.get() || kind == PROVENANCE_RECOVERY)
+        {
+            self.warranties.setter(key).holder.set(to);
+        }
+
+        let manufacturer = self.items.getter(key).manufacturer_address.get();
+        let mut stats = self.manufacturer_stats.setter(manufacturer);
+        let transferred = stats.total_transferred.get();
+        stats.total_transferred.set(transferred + U256::from(1));
+
+        log(
+            self.vm(),
+            Transfer {
+                from,
+                to,
+                tokenId: token_id(key),
+            },
+        );
+    }
+
+    // ERC-721 transfers follow the same rules as ownership codes: only the owner or someone it
+    // approved moves an item, and only to a registered user
+    fn transfer_token(
+        &mut self,
+        from: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), EriError> {
         self.is_authenticity_set()?;
-        self.address_zero_check(caller)?;
-        self.is_registered(caller)?;
+        self.address_zero_check(to)?;
+        self.is_registered(to)?;
+        self.touch();
 
-        let temp_owner = self.temp.get(item_hash);
+        let key = token_key(token_id);
+        let owner = self.is_owner_or_approved(key)?;
 
-        let mut item_guard = self.temp_owners.setter(item_hash);
-        let item = item_guard.setter(temp_owner);
+        if from != owner {
+            return Err(OnlyOwner(ONLY_OWNER { owner: from }));
+        }
 
-        if item.owner.get().is_zero() {
-            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        if from == to {
+            return Err(InvalidRecipient(INVALID_RECIPIENT { to }));
         }
 
-        if item.owner.get() != caller {
-            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        self.is_transferable(key)?;
+
+        // a direct transfer supersedes any code the owner handed out for the item
+        let pending_code = self.pending_codes.get(key);
+        if !self.offers.get(pending_code).creator.get().is_zero() {
+            self.clear_offer(pending_code);
         }
 
-        self.temp_owners.setter(item_hash).delete(temp_owner);
-        self.temp.delete(item_hash);
+        self.move_item(key, from, to, PROVENANCE_GIFT);
 
-        stylus_sdk::evm::log(CodeRevoked {
-            itemHash: item_hash,
-        });
+        log(
+            self.vm(),
+            OwnershipClaimed {
+                newOwner: to,
+                oldOwner: from,
+            },
+        );
 
         Ok(())
     }
 
-    fn get_item(
-        &self,
-        item_id: String,
-    ) -> Result<(String, String, String, U256, Address, String, Vec<String>), EriError> {
-        self.is_authenticity_set()?;
+    // my_item_indexes holds position + 1 so that zero means "not in the list"
+    fn add_to_my_items(&mut self, owner: Address, key: FixedBytes<32>) {
+        let mut item_list = self.my_items.setter(owner);
+        item_list.push(key);
+        let len = item_list.len();
+
+        self.my_item_indexes
+            .setter(owner)
+            .setter(key)
+            .set(U256::from(len));
+    }
 
-        let user = self.owners.get(item_id.clone());
+    fn remove_from_my_items(&mut self, owner: Address, key: FixedBytes<32>) {
+        let position = self.my_item_indexes.getter(owner).get(key);
 
-        if user.is_zero() {
-            return Err(ItemDoesNotExist(ITEM_DOESNT_EXIST {
-                itemId: item_id.clone(),
-            }));
+        if position.is_zero() {
+            return;
         }
-        let item_guard = self.owned_items.getter(user);
-        let item = item_guard.getter(item_id);
 
-        let mut meta = Vec::new();
+        let index = position.to::<usize>() - 1;
+        let mut item_list = self.my_items.setter(owner);
+        let last = item_list.len() - 1;
 
-        for i in 0..item.metadata.len() {
-            meta.push(item.metadata.get(i).unwrap().get_string())
+        // move the last id into the freed slot so the list stays dense
+        if index != last {
+            let last_key = item_list.get(last).unwrap();
+            item_list.setter(index).unwrap().set(last_key);
+
+            self.my_item_indexes
+                .setter(owner)
+                .setter(last_key)
+                .set(position);
         }
 
-        Ok(item_tuple(&item, meta))
+        self.my_items.setter(owner).pop();
+        self.my_item_indexes.setter(owner).delete(key);
     }
 
-    fn verify_ownership(
+    // pages through the owner's list; filters only drop entries inside the requested page so
+    // the cost of a call stays bounded by `limit`
+    fn items_of(
         &self,
-        item_id: String,
-    ) -> Result<(String, String, String, Address), EriError> {
-        self.is_authenticity_set()?;
+        owner: Address,
+        offset: U256,
+        limit: U256,
+        keep: impl Fn(&StorageGuard<Item>) -> bool,
+    ) -> Vec<ItemTuple> {
+        let item_list = self.my_items.get(owner);
+        let (start, end) = page_bounds(item_list.len(), offset, limit);
+
+        let mut new_list = Vec::new();
 
-        let user = self.owners.get(item_id.clone());
+        for i in start..end {
+            let item = self.items.getter(item_list.get(i).unwrap());
 
-        if user.is_zero() {
-            return Err(ItemDoesNotExist(ITEM_DOESNT_EXIST {
-                itemId: item_id.clone(),
-            }));
+            if !keep(&item) {
+                continue;
+            }
+
+            let mut meta = Vec::new();
+
+            for i in 0..item.metadata.len() {
+                meta.push(item.metadata.get(i).unwrap().get_string())
+            }
+
+            new_list.push(item_tuple(&item, meta))
         }
 
-        let item_guard = self.owned_items.getter(user);
-        let item = item_guard.getter(item_id);
+        new_list
+    }
 
-        Ok((
+    fn ownership_of(&self, key: FixedBytes<32>) -> OwnershipTuple {
+        let item = self.items.getter(key);
+
+        (
             item.name.get_string(),
             item.item_id.get_string(),
             self.usernames.getter(item.owner.get()).get_string(),
             item.owner.get(),
+            item.stolen.get(),
+            self.current_user(key),
+        )
+    }
+
+    fn pending_transfer(&self, key: FixedBytes<32>) -> Result<PendingTransferTuple, EriError> {
+        let item_hash = self.pending_codes.get(key);
+        let offer = self.offers.get(item_hash);
+
+        if offer.creator.get().is_zero() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        Ok((
+            self.temp.get(item_hash), //recipient
+            offer.creator.get(),      //creator
+            offer.expires_at.get(),   //expiry, the code may already have lapsed
+            offer.price.get(),        //sale price, zero for a gift
+            offer.token.get(),        //payment token, zero for native ETH
         ))
     }
 
-    fn is_owner(&self, user: Address, item_id: String) -> Result<bool, EriError> {
+    fn history_of(&self, key: FixedBytes<32>, offset: U256, limit: U256) -> Vec<ProvenanceTuple> {
+        let history = self.provenance.get(key);
+        let (start, end) = page_bounds(history.len(), offset, limit);
+
+        let mut records = Vec::new();
+
+        for i in start..end {
+            let record = history.get(i).unwrap();
+
+            records.push((
+                record.owner.get(),
+                record.acquired_at.get(),
+                record.kind.get().to::<u8>(),
+            ));
+        }
+
+        records
+    }
+
+    // the custodian holding an item, zero once the lending period is over
+    fn current_user(&self, key: FixedBytes<32>) -> Address {
+        let item_user = self.item_users.get(key);
+
+        if U256::from(self.vm().block_timestamp()) >= item_user.expires.get() {
+            return Address::ZERO;
+        }
+
+        item_user.user.get()
+    }
+
+    fn clear_user(&mut self, key: FixedBytes<32>) {
+        if self.item_users.get(key).user.get().is_zero() {
+            return;
+        }
+
+        self.item_users.delete(key);
+
+        log(
+            self.vm(),
+            UpdateUser {
+                tokenId: token_id(key),
+                user: Address::ZERO,
+                expires: 0,
+            },
+        );
+    }
+
+    fn record_provenance(&mut self, key: FixedBytes<32>, owner: Address, kind: u8) {
+        let now = U256::from(self.vm().block_timestamp());
+
+        let mut history = self.provenance.setter(key);
+        let mut record = history.grow();
+
+        record.owner.set(owner);
+        record.acquired_at.set(now);
+        record.kind.set(U8::from(kind));
+    }
+
+    // bumping the nonce voids every approval given for the previous request
+    fn reset_recovery(&mut self, account: Address) {
+        let mut recovery = self.recoveries.setter(account);
+        let nonce = recovery.nonce.get();
+
+        recovery.new_address.set(Address::ZERO);
+        recovery.approvals.set(U256::ZERO);
+        recovery.ready_at.set(U256::ZERO);
+        recovery.votes.set(U256::ZERO);
+        recovery.nonce.set(nonce + U256::from(1));
+    }
+
+    fn clear_guardians(&mut self, account: Address) {
+        let count = self.guardians.get(account).len();
+
+        for i in 0..count {
+            let guardian = self.guardians.getter(account).get(i).unwrap();
+            self.is_guardian.setter(account).delete(guardian);
+        }
+
+        self.guardians.setter(account).erase();
+        self.guardian_thresholds.delete(account);
+    }
+
+    // shared by the bare and manufacturer-qualified code generators. A code either names
+    // its recipient or, with a secret, is open to whoever reveals it
+    fn generate_code(
+        &mut self,
+        key: FixedBytes<32>,
+        temp_owner: Address,
+        secret_hash: FixedBytes<32>,
+        token: Address,
+        price: U256,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.address_zero_check(caller)?;
+        if secret_hash.is_zero() {
+            self.address_zero_check(temp_owner)?;
+        }
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+        let owner = self.is_owner_or_approved(key)?;
+
+        if owner == temp_owner {
+            return Err(CannotGenerate(CANNOT_GENERATE_CODE_FOR_YOURSELF { caller }));
+        }
+
+        self.issue_offer(owner, key, temp_owner, secret_hash, token, price)
+    }
+
+    fn store_warranty(
+        &mut self,
+        key: FixedBytes<32>,
+        start: U256,
+        duration: U256,
+        terms_uri: String,
+        transferable: bool,
+    ) -> Result<(), EriError> {
+        let owner = self.live_owner(key)?;
+
+        self.is_item_manufacturer(key)?;
+
+        if duration.is_zero() {
+            return Err(InvalidDuration(INVALID_DURATION {}));
+        }
+
+        let start = if start.is_zero() {
+            self.provenance.get(key).get(0).unwrap().acquired_at.get()
+        } else {
+            start
+        };
+
+        let mut warranty = self.warranties.setter(key);
+        warranty.start.set(start);
+        warranty.duration.set(duration);
+        warranty.terms_uri.set_str(terms_uri);
+        warranty.transferable.set(transferable);
+        warranty.holder.set(owner);
+
+        log(
+            self.vm(),
+            WarrantySet {
+                itemKey: key,
+                start,
+                duration,
+                transferable,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn warranty_of(&self, key: FixedBytes<32>) -> Result<WarrantyTuple, EriError> {
+        let warranty = self.warranties.get(key);
+
+        if warranty.duration.get().is_zero() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        let expires_at = warranty.start.get() + warranty.duration.get();
+        let now = U256::from(self.vm().block_timestamp());
+
+        // it only covers the item while the holder still owns it
+        let active = now <= expires_at && warranty.holder.get() == self.item_owner(key);
+
+        Ok((
+            warranty.start.get(),
+            expires_at,
+            warranty.terms_uri.get_string(),
+            warranty.transferable.get(),
+            warranty.holder.get(),
+            active,
+        ))
+    }
+
+    fn record_service(
+        &mut self,
+        key: FixedBytes<32>,
+        service_type: String,
+        description_hash: FixedBytes<32>,
+        uri: String,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.live_owner(key)?;
+
+        let manufacturer = self.items.getter(key).manufacturer_address.get();
+
+        if !self.is_service_centre(manufacturer, caller) {
+            return Err(NotServiceCentre(NOT_SERVICE_CENTRE { caller }));
+        }
+
+        let now = U256::from(self.vm().block_timestamp());
+
+        let mut records = self.service_records.setter(key);
+        let mut record = records.grow();
+
+        record.centre.set(caller);
+        record.timestamp.set(now);
+        record.service_type.set_str(service_type.clone());
+        record.description_hash.set(description_hash);
+        record.uri.set_str(uri);
+
+        log(
+            self.vm(),
+            ServiceRecorded {
+                itemKey: key,
+                centre: caller,
+                serviceType: service_type,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn service_records_of(
+        &self,
+        key: FixedBytes<32>,
+        offset: U256,
+        limit: U256,
+    ) -> Vec<ServiceTuple> {
+        let records = self.service_records.get(key);
+        let (start, end) = page_bounds(records.len(), offset, limit);
+
+        let mut list = Vec::new();
+
+        for i in start..end {
+            let record = records.get(i).unwrap();
+
+            list.push((
+                record.centre.get(),
+                record.timestamp.get(),
+                record.service_type.get_string(),
+                record.description_hash.get(),
+                record.uri.get_string(),
+            ));
+        }
+
+        list
+    }
+
+    fn set_locked(&mut self, key: FixedBytes<32>, locked: bool) -> Result<(), EriError> {
+        self.is_item_manufacturer(key)?;
+
+        let was_locked = self.is_locked(key);
+
+        self.locked_items.setter(key).set(locked);
+
+        // a model lock keeps the item locked whatever its own flag says, so ERC-5192 events
+        // are only emitted when the effective status changes
+        let token_id = token_id(key);
+
+        match (was_locked, self.is_locked(key)) {
+            (false, true) => log(self.vm(), Locked { tokenId: token_id }),
+            (true, false) => log(self.vm(), Unlocked { tokenId: token_id }),
+            _ => {}
+        }
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn set_decommission_request(
+        &mut self,
+        key: FixedBytes<32>,
+        requested: bool,
+    ) -> Result<(), EriError> {
+        let owner = self.live_owner(key)?;
+
+        self.is_item_owner(key)?;
+
+        self.decommission_requests.setter(key).set(requested);
+
+        log(
+            self.vm(),
+            DecommissionRequested {
+                itemKey: key,
+                owner,
+                requested,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // the owner retires their own item, the manufacturer only confirms a take-back the owner
+    // asked for
+    fn decommission(&mut self, key: FixedBytes<32>, reason: String) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        let owner = self.live_owner(key)?;
+
+        let confirms_request =
+            self.decommission_requests.get(key) && self.is_item_manufacturer(key).is_ok();
+
+        if !self.acts_for(owner, caller) && !confirms_request {
+            return Err(Unauthorized(UNAUTHORIZED { caller }));
+        }
+
+        self.decommission_requests.delete(key);
+
+        let pending_code = self.pending_codes.get(key);
+        if !self.offers.get(pending_code).creator.get().is_zero() {
+            self.clear_offer(pending_code);
+        }
+
+        self.token_approvals.delete(key);
+        self.clear_user(key);
+        self.remove_from_my_items(owner, key);
+
+        let balance = self.balances.get(owner);
+        self.balances.setter(owner).set(balance - U256::from(1));
+
+        // the item record stays readable, but with no owner it can never move or be claimed again
+        let mut item = self.items.setter(key);
+        item.owner.set(Address::ZERO);
+        item.decommissioned.set(true);
+
+        self.record_provenance(key, Address::ZERO, PROVENANCE_DECOMMISSIONED);
+
+        log(
+            self.vm(),
+            Transfer {
+                from: owner,
+                to: Address::ZERO,
+                tokenId: token_id(key),
+            },
+        );
+
+        log(
+            self.vm(),
+            ItemDecommissioned {
+                itemKey: key,
+                by: caller,
+                reason,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn set_stolen(&mut self, key: FixedBytes<32>, stolen: bool) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
         self.is_authenticity_set()?;
+        self.is_item_owner(key)?;
+
+        self.items.setter(key).stolen.set(stolen);
+
+        // codes handed out before the theft must not be redeemable by whoever holds them
+        let pending_code = self.pending_codes.get(key);
+        if stolen && !self.offers.get(pending_code).creator.get().is_zero() {
+            self.clear_offer(pending_code);
+        }
+
+        log(
+            self.vm(),
+            StolenStatusChanged {
+                itemKey: key,
+                owner: caller,
+                stolen,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // sends an account's proceeds in `token` (zero for ETH) to `recipient`
+    fn pay_out(
+        &mut self,
+        account: Address,
+        recipient: Address,
+        token: Address,
+    ) -> Result<(), EriError> {
+        if self.is_recovered(account) {
+            return Err(Unauthorized(UNAUTHORIZED {
+                caller: self.vm().msg_sender(),
+            }));
+        }
+
+        let amount = if token.is_zero() {
+            self.proceeds.get(account)
+        } else {
+            self.token_proceeds.getter(token).get(account)
+        };
+
+        if amount.is_zero() {
+            return Err(NothingToWithdraw(NOTHING_TO_WITHDRAW {}));
+        }
+
+        self.lock()?;
+
+        // the balance is cleared before the transfer so the payout cannot be repeated
+        let sent = if token.is_zero() {
+            self.proceeds.delete(account);
+
+            match self.vm().transfer_eth(recipient, amount) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(WithdrawalFailed(WITHDRAWAL_FAILED {})),
+            }
+        } else {
+            self.token_proceeds.setter(token).delete(account);
+
+            safe_transfer(self.vm(), token, recipient, amount)
+        };
+
+        self.unlock();
+
+        sent?;
+
+        log(
+            self.vm(),
+            ProceedsWithdrawn {
+                account,
+                token,
+                amount,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn set_role(&mut self, organisation: Address, member: Address, role: u8) {
+        let previous = self.org_roles.getter(organisation).get(member).to::<u8>();
+
+        let admin_count = self.organisations.get(organisation).admin_count.get();
+        if previous == ROLE_ADMIN && role != ROLE_ADMIN {
+            self.organisations
+                .setter(organisation)
+                .admin_count
+                .set(admin_count - U256::from(1));
+        } else if previous != ROLE_ADMIN && role == ROLE_ADMIN {
+            self.organisations
+                .setter(organisation)
+                .admin_count
+                .set(admin_count + U256::from(1));
+        }
+
+        // members are kept dense the same way owner item lists are
+        if previous == ROLE_NONE && role != ROLE_NONE {
+            let mut members = self.org_members.setter(organisation);
+            members.push(member);
+            let len = members.len();
+
+            self.org_member_indexes
+                .setter(organisation)
+                .setter(member)
+                .set(U256::from(len));
+
+            // and the reverse, so a member's organisations can follow them to a new address
+            let mut orgs = self.member_orgs.setter(member);
+            orgs.push(organisation);
+            let len = orgs.len();
+
+            self.member_org_indexes
+                .setter(member)
+                .setter(organisation)
+                .set(U256::from(len));
+        } else if previous != ROLE_NONE && role == ROLE_NONE {
+            let position = self.org_member_indexes.getter(organisation).get(member);
+            let index = position.to::<usize>() - 1;
+            let mut members = self.org_members.setter(organisation);
+            let last = members.len() - 1;
+
+            if index != last {
+                let last_member = members.get(last).unwrap();
+                members.setter(index).unwrap().set(last_member);
+
+                self.org_member_indexes
+                    .setter(organisation)
+                    .setter(last_member)
+                    .set(position);
+            }
+
+            self.org_members.setter(organisation).pop();
+            self.org_member_indexes.setter(organisation).delete(member);
+
+            let position = self.member_org_indexes.getter(member).get(organisation);
+            let index = position.to::<usize>() - 1;
+            let mut orgs = self.member_orgs.setter(member);
+            let last = orgs.len() - 1;
+
+            if index != last {
+                let last_org = orgs.get(last).unwrap();
+                orgs.setter(index).unwrap().set(last_org);
+
+                self.member_org_indexes
+                    .setter(member)
+                    .setter(last_org)
+                    .set(position);
+            }
+
+            self.member_orgs.setter(member).pop();
+            self.member_org_indexes.setter(member).delete(organisation);
+        }
+
+        self.org_roles
+            .setter(organisation)
+            .setter(member)
+            .set(U8::from(role));
+
+        log(
+            self.vm(),
+            MemberRoleSet {
+                organisation,
+                member,
+                role,
+            },
+        );
+    }
+
+    // an organisation whose only admin leaves could never move its items again
+    fn is_last_admin(&self, member: Address) -> bool {
+        let orgs = self.member_orgs.get(member);
+
+        (0..orgs.len()).any(|i| {
+            let organisation = orgs.get(i).unwrap();
+
+            self.is_org_admin(organisation, member)
+                && self.organisations.get(organisation).admin_count.get() == U256::from(1)
+        })
+    }
+
+    // drops every role `member` holds, handing each to `to` unless it is zero
+    fn move_memberships(&mut self, member: Address, to: Address) {
+        while !self.member_orgs.get(member).is_empty() {
+            let orgs = self.member_orgs.get(member);
+            let organisation = orgs.get(orgs.len() - 1).unwrap();
+            let role = self.org_roles.getter(organisation).get(member).to::<u8>();
+
+            // granting first keeps the admin count from touching zero on the way
+            if !to.is_zero() {
+                self.set_role(organisation, to, role);
+            }
+
+            self.set_role(organisation, member, ROLE_NONE);
+        }
+    }
+
+    // hands up to `limit` items of `from` to `to`, dropping codes that were pending on them.
+    // Items `stays` picks out are left with `from`. The list is walked from the end, so the
+    // entry swapped into a freed slot has always been looked at already
+    fn move_items(
+        &mut self,
+        from: Address,
+        to: Address,
+        kind: u8,
+        limit: usize,
+        stays: impl Fn(&Self, FixedBytes<32>) -> bool,
+    ) -> usize {
+        let mut index = self.my_items.get(from).len();
+        let mut moved = 0;
+
+        while index > 0 && moved < limit {
+            index -= 1;
+            let key = self.my_items.getter(from).get(index).unwrap();
+
+            if stays(self, key) {
+                continue;
+            }
+
+            let pending_code = self.pending_codes.get(key);
+            if !self.offers.get(pending_code).creator.get().is_zero() {
+                self.clear_offer(pending_code);
+            }
+
+            self.move_item(key, from, to, kind);
+            moved += 1;
+        }
+
+        moved
+    }
+
+    // moves an account's balance in `token` (zero for ETH) to where it was recovered to
+    fn migrate_proceeds(&mut self, account: Address, token: Address) -> U256 {
+        let amount = if token.is_zero() {
+            let amount = self.proceeds.get(account);
+            self.proceeds.delete(account);
+            amount
+        } else {
+            let amount = self.token_proceeds.getter(token).get(account);
+            self.token_proceeds.setter(token).delete(account);
+            amount
+        };
+
+        self.credit(token, account, amount);
+
+        amount
+    }
+
+    // anything the owner does through the contract proves they are still around
+    fn touch(&mut self) {
+        let caller = self.vm().msg_sender();
+        let now = U256::from(self.vm().block_timestamp());
+
+        self.last_active.setter(caller).set(now);
+    }
+
+    fn clear_offer(&mut self, item_hash: FixedBytes<32>) {
+        let key = self.offers.get(item_hash).item_key.get();
+
+        self.temp.delete(item_hash);
+        self.offers.delete(item_hash);
+        self.pending_codes.delete(key);
+    }
+}
+
+#[public]
+impl Ownership {
+    #[constructor]
+    fn constructor(&mut self, owner: Address) -> Result<(), EriError> {
+        self.address_zero_check(owner)?;
+
+        self.owner.set(owner);
+        self.transfer_duration
+            .set(U256::from(DEFAULT_TRANSFER_DURATION));
+
+        log(
+            self.vm(),
+            ContractCreated {
+                contractAddress: self.vm().contract_address(),
+                owner,
+            },
+        );
+
+        Ok(())
+    }
+    fn set_authenticity(&mut self, authenticity_address: Address) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+        self.address_zero_check(authenticity_address)?;
+        let caller = self.vm().msg_sender();
+        //ONLY OWNER
+        if caller != self.owner.get() {
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        }
+
+        self.owner.set(authenticity_address);
+
+        log(
+            self.vm(),
+            AuthenticitySet {
+                authenticityAddress: authenticity_address,
+            },
+        );
+
+        Ok(())
+    }
+    fn set_transfer_duration(&mut self, duration: U256) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        //ONLY OWNER
+        if caller != self.owner.get() {
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        }
+
+        if duration.is_zero() {
+            return Err(InvalidDuration(INVALID_DURATION {}));
+        }
+
+        self.transfer_duration.set(duration);
+
+        log(self.vm(), TransferDurationSet { duration });
+
+        Ok(())
+    }
+    fn get_transfer_duration(&self) -> U256 {
+        self.transfer_duration.get()
+    }
+    fn set_base_uri(&mut self, base_uri: String) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        //ONLY OWNER
+        if caller != self.owner.get() {
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        }
+
+        self.base_uri.set_str(base_uri.clone());
+
+        log(self.vm(), BaseURISet { baseURI: base_uri });
+
+        Ok(())
+    }
+    fn user_registers(&mut self, username: String) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+
+        // a recovered key stays retired, whoever holds it now
+        if self.is_recovered(caller) {
+            return Err(Unauthorized(UNAUTHORIZED { caller }));
+        }
+
+        let time = self.vm().block_timestamp();
+
+        if username.len() < 3 {
+            return Err(BadUsername(USERNAME_MUST_BE_AT_LEAST_3_LETTERS {}));
+        }
+
+        let mut user = self.users.setter(username.clone());
+
+        if user.registered.get() {
+            return Err(NotAvailable(NAME_NOT_AVAILABLE {
+                username: username.clone(),
+            }));
+        }
+
+        let mut fetched_username = self.usernames.setter(caller);
+        if !fetched_username.get_string().is_empty() {
+            return Err(Registered(ALREADY_REGISTERED { caller }));
+        }
+
+        user.user_address.set(caller);
+        user.username.set_str(username.clone());
+        user.registered.set(true);
+        user.registered_at.set(U256::from(time));
+
+        fetched_username.set_str(username.clone());
+
+        log(
+            self.vm(),
+            UserRegistered {
+                userAddress: caller,
+                username: keccak(username.as_bytes()),
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+    fn get_user(&self, user_address: Address) -> Result<(Address, String, bool, U256), EriError> {
+        self.is_authenticity_set()?;
+        let username = self.usernames.get(user_address);
+        let user = self.users.get(username.get_string());
+
+        if user.user_address.get().is_zero() {
+            return Err(NotExist(USER_DOES_NOT_EXIST { user: user_address }));
+        }
+
+        Ok((
+            user.user_address.get(),    //user address
+            user.username.get_string(), //user name
+            user.registered.get(),      //is_registered
+            user.registered_at.get(),   //reg time
+        ))
+    }
+
+    fn change_username(&mut self, new_username: String) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        if new_username.len() < 3 {
+            return Err(BadUsername(USERNAME_MUST_BE_AT_LEAST_3_LETTERS {}));
+        }
+
+        if self.users.get(new_username.clone()).registered.get() {
+            return Err(NotAvailable(NAME_NOT_AVAILABLE {
+                username: new_username,
+            }));
+        }
+
+        let old_username = self.usernames.get(caller).get_string();
+        let old = self.users.get(old_username.clone());
+
+        let registered_at = old.registered_at.get();
+        let avatar_uri = old.avatar_uri.get_string();
+        let contact_hash = old.contact_hash.get();
+        let preferred_language = old.preferred_language.get_string();
+
+        // the old name is released for anyone else to take
+        self.users.delete(old_username.clone());
+
+        let mut user = self.users.setter(new_username.clone());
+        user.user_address.set(caller);
+        user.username.set_str(new_username.clone());
+        user.registered.set(true);
+        user.registered_at.set(registered_at);
+        user.avatar_uri.set_str(avatar_uri);
+        user.contact_hash.set(contact_hash);
+        user.preferred_language.set_str(preferred_language);
+
+        self.usernames.setter(caller).set_str(new_username.clone());
+
+        log(
+            self.vm(),
+            UsernameChanged {
+                userAddress: caller,
+                oldUsername: keccak(old_username.as_bytes()),
+                newUsername: keccak(new_username.as_bytes()),
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn set_profile(
+        &mut self,
+        avatar_uri: String,
+        contact_hash: FixedBytes<32>,
+        preferred_language: String,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        let username = self.usernames.get(caller).get_string();
+        let mut user = self.users.setter(username);
+
+        user.avatar_uri.set_str(avatar_uri);
+        user.contact_hash.set(contact_hash);
+        user.preferred_language.set_str(preferred_language);
+
+        log(
+            self.vm(),
+            ProfileUpdated {
+                userAddress: caller,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // (avatar uri, contact hash, preferred language)
+    fn get_profile(
+        &self,
+        user_address: Address,
+    ) -> Result<(String, FixedBytes<32>, String), EriError> {
+        self.is_registered(user_address)?;
+
+        let user = self
+            .users
+            .get(self.usernames.get(user_address).get_string());
+
+        Ok((
+            user.avatar_uri.get_string(),
+            user.contact_hash.get(),
+            user.preferred_language.get_string(),
+        ))
+    }
+
+    fn deregister(&mut self) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        let count = self.balances.get(caller);
+
+        if !count.is_zero() {
+            return Err(StillOwnsItems(STILL_OWNS_ITEMS { count }));
+        }
+
+        if self.is_last_admin(caller) {
+            return Err(LastAdmin(LAST_ADMIN {}));
+        }
+
+        // approvals cannot be enumerated, so the operators have to be revoked first
+        let operators = self.operator_counts.get(caller);
+
+        if !operators.is_zero() {
+            return Err(OperatorsApproved(OPERATORS_STILL_APPROVED {
+                count: operators,
+            }));
+        }
+
+        // an unregistered address keeps no say over any organisation
+        self.move_memberships(caller, Address::ZERO);
+
+        // nor any recovery or inheritance set up, should the address register again
+        self.clear_guardians(caller);
+        self.reset_recovery(caller);
+        self.inheritances.delete(caller);
+        self.last_active.delete(caller);
+
+        let username = self.usernames.get(caller).get_string();
+
+        self.users.delete(username);
+        self.usernames.delete(caller);
+
+        log(
+            self.vm(),
+            UserDeregistered {
+                userAddress: caller,
+            },
+        );
+
+        Ok(())
+    }
+
+    // an organisation is an account without a key of its own; its name shares the username
+    // namespace and its admins act for it
+    fn create_organisation(&mut self, name: String) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        if name.len() < 3 {
+            return Err(BadUsername(USERNAME_MUST_BE_AT_LEAST_3_LETTERS {}));
+        }
+
+        let organisation = organisation_address(&name);
+
+        if self.users.get(name.clone()).registered.get() || self.is_registered(organisation).is_ok()
+        {
+            return Err(NotAvailable(NAME_NOT_AVAILABLE { username: name }));
+        }
+
+        let now = U256::from(self.vm().block_timestamp());
+
+        let mut user = self.users.setter(name.clone());
+        user.user_address.set(organisation);
+        user.username.set_str(name.clone());
+        user.registered.set(true);
+        user.registered_at.set(now);
+
+        self.usernames.setter(organisation).set_str(name.clone());
+
+        let mut org = self.organisations.setter(organisation);
+        org.name.set_str(name.clone());
+        org.created_at.set(now);
+
+        log(
+            self.vm(),
+            OrganisationCreated {
+                organisation,
+                creator: caller,
+                name,
+            },
+        );
+
+        self.set_role(organisation, caller, ROLE_ADMIN);
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn set_member_role(
+        &mut self,
+        organisation: Address,
+        member: Address,
+        role: u8,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+
+        if !self.is_org_admin(organisation, caller) {
+            return Err(NotOrgAdmin(NOT_ORGANISATION_ADMIN { caller }));
+        }
+
+        if !matches!(role, ROLE_NONE | ROLE_MEMBER | ROLE_ADMIN) {
+            return Err(InvalidRole(INVALID_ROLE { role }));
+        }
+
+        if role != ROLE_NONE {
+            self.is_registered(member)?;
+        }
+
+        // an organisation without admins could never move its items again
+        if self.is_org_admin(organisation, member)
+            && role != ROLE_ADMIN
+            && self.organisations.get(organisation).admin_count.get() == U256::from(1)
+        {
+            return Err(LastAdmin(LAST_ADMIN {}));
+        }
+
+        self.set_role(organisation, member, role);
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // (name, members, their roles)
+    fn get_organisation(
+        &self,
+        organisation: Address,
+    ) -> Result<(String, Vec<Address>, Vec<u8>), EriError> {
+        let org = self.organisations.get(organisation);
+
+        if org.admin_count.get().is_zero() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        let list = self.org_members.get(organisation);
+
+        let mut members = Vec::new();
+        let mut roles = Vec::new();
+
+        for i in 0..list.len() {
+            let member = list.get(i).unwrap();
+
+            members.push(member);
+            roles.push(self.org_roles.getter(organisation).get(member).to::<u8>());
+        }
+
+        Ok((org.name.get_string(), members, roles))
+    }
+
+    fn get_member_role(&self, organisation: Address, member: Address) -> u8 {
+        self.org_roles.getter(organisation).get(member).to::<u8>()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_item(
+        &mut self,
+        user: Address,
+        name: String,
+        unique_id: String,
+        serial: String,
+        date: U256,
+        owner: Address,
+        metadata: Vec<String>,
+        manufacturer_name: String,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+        self.address_zero_check(user)?;
+        self.is_registered(user)?;
+
+        if caller != self.authenticity.get() {
+            //making sure only Authenticity can call this function
+            return Err(Unauthorized(UNAUTHORIZED { caller }));
+        }
+        if owner.is_zero() {
+            return Err(AddressZero(ADDRESS_ZERO { zero: owner }));
+        }
+
+        // ids only have to be unique per manufacturer, so one brand cannot squat another's
+        let key = item_key(owner, &unique_id);
+
+        if !self.item_owner(key).is_zero() {
+            return Err(AlreadyClaimed(ITEM_CLAIMED_ALREADY {
+                itemId: unique_id.clone(),
+            }));
+        }
+
+        if self.items.getter(key).decommissioned.get() {
+            return Err(Decommissioned(ITEM_DECOMMISSIONED {
+                itemId: unique_id.clone(),
+            }));
+        }
+
+        // a bare id resolves only while a single manufacturer has issued it
+        if self.item_keys.get(unique_id.clone()).is_zero() {
+            self.item_keys.setter(unique_id.clone()).set(key);
+        } else {
+            self.ambiguous_ids.setter(unique_id.clone()).set(true);
+        }
+
+        //======== GENERAL ITEMS ==========
+        let mut item = self.items.setter(key);
+
+        set_item(
+            &mut item,
+            user,
+            name,
+            unique_id.clone(),
+            serial,
+            date,
+            manufacturer_name,
+            owner,
+            metadata,
+        );
+
+        //======== PERSONAL ITEM =============
+        self.add_to_my_items(user, key);
+
+        let balance = self.balances.get(user);
+        self.balances.setter(user).set(balance + U256::from(1));
+
+        self.record_provenance(key, user, PROVENANCE_MINT);
+
+        self.manufacturer_items.setter(owner).push(key);
+
+        let mut stats = self.manufacturer_stats.setter(owner);
+        let claimed = stats.total_claimed.get();
+        stats.total_claimed.set(claimed + U256::from(1));
+
+        log(
+            self.vm(),
+            Transfer {
+                from: Address::ZERO,
+                to: user,
+                tokenId: token_id(key),
+            },
+        );
+
+        // ERC-5192 expects a lock to be announced when a soulbound token is minted
+        if self.is_locked(key) {
+            log(
+                self.vm(),
+                Locked {
+                    tokenId: token_id(key),
+                },
+            );
+        }
+
+        log(
+            self.vm(),
+            ItemCreated {
+                itemId: keccak(unique_id.as_bytes()),
+                owner: user,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn get_all_my_items(&self) -> Result<Vec<ItemTuple>, EriError> {
+        self.is_authenticity_set()?;
+
+        let caller = self.vm().msg_sender();
+
+        if self
+            .users
+            .get(self.usernames.get(caller).get_string())
+            .user_address
+            .get()
+            .is_zero()
+        {
+            return Err(NotExist(USER_DOES_NOT_EXIST { user: caller }));
+        }
+
+        Ok(self.items_of(caller, U256::ZERO, U256::MAX, |_| true))
+    }
+
+    // the organisation counterpart of get_all_my_items, for any of its members
+    fn get_organisation_items(
+        &self,
+        organisation: Address,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ItemTuple>, EriError> {
+        self.is_authenticity_set()?;
+
+        let caller = self.vm().msg_sender();
+
+        if self.get_member_role(organisation, caller) == ROLE_NONE {
+            return Err(NotOrgMember(NOT_ORGANISATION_MEMBER { caller }));
+        }
+
+        Ok(self.items_of(organisation, offset, limit, |_| true))
+    }
+
+    fn get_items_of(
+        &self,
+        owner: Address,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ItemTuple>, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.items_of(owner, offset, limit, |_| true))
+    }
+
+    fn item_count_of(&self, owner: Address) -> U256 {
+        U256::from(self.my_items.get(owner).len())
+    }
+
+    fn get_items_of_by_manufacturer(
+        &self,
+        owner: Address,
+        manufacturer: Address,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ItemTuple>, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.items_of(owner, offset, limit, |item| {
+            item.manufacturer_address.get() == manufacturer
+        }))
+    }
+
+    // `from` and `to` are inclusive bounds on the item's date
+    fn get_items_of_by_date(
+        &self,
+        owner: Address,
+        from: U256,
+        to: U256,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ItemTuple>, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.items_of(owner, offset, limit, |item| {
+            item.date.get() >= from && item.date.get() <= to
+        }))
+    }
+
+    // ids of every claimed item made by `manufacturer`, in claim order
+    fn get_manufacturer_items(
+        &self,
+        manufacturer: Address,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<String>, EriError> {
+        self.is_authenticity_set()?;
+
+        let item_list = self.manufacturer_items.get(manufacturer);
+        let (start, end) = page_bounds(item_list.len(), offset, limit);
+
+        let mut ids = Vec::new();
+
+        for i in start..end {
+            ids.push(
+                self.items
+                    .getter(item_list.get(i).unwrap())
+                    .item_id
+                    .get_string(),
+            );
+        }
+
+        Ok(ids)
+    }
+
+    // (total claimed, total transferred)
+    fn get_manufacturer_stats(&self, manufacturer: Address) -> (U256, U256) {
+        let stats = self.manufacturer_stats.get(manufacturer);
+
+        (stats.total_claimed.get(), stats.total_transferred.get())
+    }
+
+    fn generate_change_of_ownership_code(
+        &mut self,
+        item_id: String,
+        temp_owner: Address,
+    ) -> Result<(), EriError> {
+        let key = self.key_of(item_id)?;
+
+        self.generate_code(key, temp_owner, FixedBytes::ZERO, Address::ZERO, U256::ZERO)
+    }
+
+    #[selector(name = "generateChangeOfOwnershipCode")]
+    fn generate_change_of_ownership_code_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        temp_owner: Address,
+    ) -> Result<(), EriError> {
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.generate_code(key, temp_owner, FixedBytes::ZERO, Address::ZERO, U256::ZERO)
+    }
+
+    fn generate_sale_code(
+        &mut self,
+        item_id: String,
+        temp_owner: Address,
+        token: Address,
+        price: U256,
+    ) -> Result<(), EriError> {
+        if price.is_zero() {
+            return Err(InvalidPrice(INVALID_PRICE {}));
+        }
+
+        let key = self.key_of(item_id)?;
+
+        self.generate_code(key, temp_owner, FixedBytes::ZERO, token, price)
+    }
+
+    #[selector(name = "generateSaleCode")]
+    fn generate_sale_code_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        temp_owner: Address,
+        token: Address,
+        price: U256,
+    ) -> Result<(), EriError> {
+        if price.is_zero() {
+            return Err(InvalidPrice(INVALID_PRICE {}));
+        }
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.generate_code(key, temp_owner, FixedBytes::ZERO, token, price)
+    }
+
+    #[payable]
+    fn new_owner_claim_ownership(&mut self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+        self.is_registered(caller)?;
+
+        self.is_offer_claimable(item_hash)?;
+
+        // an admin claims, and pays, on behalf of an organisation the code was issued to
+        let recipient = self.temp.get(item_hash);
+
+        if recipient.is_zero() || !self.acts_for(recipient, caller) {
+            return Err(NotRecipient(NOT_INTENDED_RECIPIENT { caller }));
+        }
+
+        self.lock()?;
+
+        let result = match self.collect_payment(item_hash, caller) {
+            Ok(paid) => self.complete_transfer(item_hash, recipient, paid),
+            Err(err) => Err(err),
+        };
+
+        self.unlock();
+
+        result
+    }
+
+    fn generate_open_ownership_code(
+        &mut self,
+        item_id: String,
+        secret_hash: FixedBytes<32>,
+    ) -> Result<(), EriError> {
+        if secret_hash.is_zero() {
+            return Err(InvalidSecret(INVALID_SECRET {}));
+        }
+
+        let key = self.key_of(item_id)?;
+
+        self.generate_code(key, Address::ZERO, secret_hash, Address::ZERO, U256::ZERO)
+    }
+
+    #[selector(name = "generateOpenOwnershipCode")]
+    fn generate_open_ownership_code_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        secret_hash: FixedBytes<32>,
+    ) -> Result<(), EriError> {
+        if secret_hash.is_zero() {
+            return Err(InvalidSecret(INVALID_SECRET {}));
+        }
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.generate_code(key, Address::ZERO, secret_hash, Address::ZERO, U256::ZERO)
+    }
+
+    fn generate_open_sale_code(
+        &mut self,
+        item_id: String,
+        secret_hash: FixedBytes<32>,
+        token: Address,
+        price: U256,
+    ) -> Result<(), EriError> {
+        if secret_hash.is_zero() {
+            return Err(InvalidSecret(INVALID_SECRET {}));
+        }
+
+        if price.is_zero() {
+            return Err(InvalidPrice(INVALID_PRICE {}));
+        }
+
+        let key = self.key_of(item_id)?;
+
+        self.generate_code(key, Address::ZERO, secret_hash, token, price)
+    }
+
+    #[selector(name = "generateOpenSaleCode")]
+    fn generate_open_sale_code_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        secret_hash: FixedBytes<32>,
+        token: Address,
+        price: U256,
+    ) -> Result<(), EriError> {
+        if secret_hash.is_zero() {
+            return Err(InvalidSecret(INVALID_SECRET {}));
+        }
+
+        if price.is_zero() {
+            return Err(InvalidPrice(INVALID_PRICE {}));
+        }
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.generate_code(key, Address::ZERO, secret_hash, token, price)
+    }
+
+    #[payable]
+    fn commit_ownership_claim(
+        &mut self,
+        item_hash: FixedBytes<32>,
+        commitment: FixedBytes<32>,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+        self.is_registered(caller)?;
+        self.is_offer_claimable(item_hash)?;
+
+        if self.offers.get(item_hash).secret_hash.get().is_zero() {
+            return Err(NotOpenCode(NOT_AN_OPEN_CODE {
+                itemHash: item_hash,
+            }));
+        }
+
+        // a zero commitment reads as "no commitment", so its deposit could be neither
+        // protected from overwriting nor refunded
+        if commitment.is_zero() {
+            return Err(InvalidCommitment(INVALID_COMMITMENT {}));
+        }
+
+        // re-committing could push an earlier reveal back, so a claimer commits once per code
+        if !self
+            .claim_commits
+            .getter(item_hash)
+            .getter(caller)
+            .commitment
+            .get()
+            .is_zero()
+        {
+            return Err(InvalidCommitment(INVALID_COMMITMENT {}));
+        }
+
+        // on a sale the price is escrowed with the commitment and refundable until revealed
+        self.lock()?;
+        let deposit = self.collect_payment(item_hash, caller);
+        self.unlock();
+
+        let deposit = deposit?;
+        let token = self.offers.get(item_hash).token.get();
+
+        let block_number = U256::from(self.vm().block_number());
+        let mut commits = self.claim_commits.setter(item_hash);
+        let mut claim = commits.setter(caller);
+
+        claim.commitment.set(commitment);
+        claim.block_number.set(block_number);
+        claim.deposit.set(deposit);
+        claim.token.set(token);
+
+        log(
+            self.vm(),
+            ClaimCommitted {
+                itemHash: item_hash,
+                commitment,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn reveal_ownership_claim(
+        &mut self,
+        item_hash: FixedBytes<32>,
+        secret: FixedBytes<32>,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+        self.is_registered(caller)?;
+        self.is_offer_claimable(item_hash)?;
+
+        let secret_hash = self.offers.get(item_hash).secret_hash.get();
+
+        if secret_hash.is_zero() {
+            return Err(NotOpenCode(NOT_AN_OPEN_CODE {
+                itemHash: item_hash,
+            }));
+        }
+
+        if keccak(secret) != secret_hash {
+            return Err(InvalidSecret(INVALID_SECRET {}));
+        }
+
+        // the commitment must have landed in an earlier block, so a secret seen in a
+        // pending reveal cannot be front-run with a fresh commitment
+        let commitment = keccak((item_hash, secret, caller).abi_encode_sequence());
+        let commits = self.claim_commits.getter(item_hash);
+        let claim = commits.getter(caller);
+
+        if claim.commitment.get() != commitment
+            || claim.block_number.get() >= U256::from(self.vm().block_number())
+        {
+            return Err(InvalidCommitment(INVALID_COMMITMENT {}));
+        }
+
+        // the code binds the terms, so the escrowed deposit already covers this offer's price
+        let deposit = claim.deposit.get();
+
+        self.claim_commits.setter(item_hash).delete(caller);
+
+        self.complete_transfer(item_hash, caller, deposit)
+    }
+
+    fn refund_claim_deposit(&mut self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.address_zero_check(caller)?;
+
+        let commits = self.claim_commits.getter(item_hash);
+        let claim = commits.getter(caller);
+
+        if claim.commitment.get().is_zero() {
+            return Err(InvalidCommitment(INVALID_COMMITMENT {}));
+        }
+
+        let deposit = claim.deposit.get();
+        let token = claim.token.get();
+
+        self.claim_commits.setter(item_hash).delete(caller);
+
+        self.credit(token, caller, deposit);
+
+        log(
+            self.vm(),
+            ClaimDepositRefunded {
+                itemHash: item_hash,
+                claimer: caller,
+                amount: deposit,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn withdraw_proceeds(&mut self) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.pay_out(caller, caller, Address::ZERO)
+    }
+
+    fn withdraw_token_proceeds(&mut self, token: Address) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.pay_out(caller, caller, token)
+    }
+
+    // an organisation holds no key, so its admins collect its sale proceeds
+    fn withdraw_organisation_proceeds(
+        &mut self,
+        organisation: Address,
+        token: Address,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        if !self.is_org_admin(organisation, caller) {
+            return Err(NotOrgAdmin(NOT_ORGANISATION_ADMIN { caller }));
+        }
+
+        self.pay_out(organisation, caller, token)
+    }
+
+    fn get_proceeds(&self, account: Address) -> U256 {
+        self.proceeds.get(account)
+    }
+
+    fn get_token_proceeds(&self, token: Address, account: Address) -> U256 {
+        self.token_proceeds.getter(token).get(account)
+    }
+
+    fn get_temp_owner(&self, item_hash: FixedBytes<32>) -> Result<Address, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.temp.get(item_hash))
+    }
+
+    fn get_pending_transfer(&self, item_id: String) -> Result<PendingTransferTuple, EriError> {
+        self.is_authenticity_set()?;
+
+        self.pending_transfer(self.key_of(item_id)?)
+    }
+
+    #[selector(name = "getPendingTransfer")]
+    fn get_pending_transfer_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<PendingTransferTuple, EriError> {
+        self.is_authenticity_set()?;
+
+        self.pending_transfer(self.qualified_key_of(manufacturer, unique_id)?)
+    }
+
+    fn owner_revoke_code(&mut self, item_hash: FixedBytes<32>) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+        self.is_registered(caller)?;
+
+        let creator = self.offers.get(item_hash).creator.get();
+
+        if creator.is_zero() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        self.is_owner_or_approved(self.offers.get(item_hash).item_key.get())?;
+
+        self.clear_offer(item_hash);
+
+        log(
+            self.vm(),
+            CodeRevoked {
+                itemHash: item_hash,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn get_item(&self, item_id: String) -> Result<ItemTuple, EriError> {
+        self.get_item_by_key(self.key_of(item_id)?)
+    }
+
+    #[selector(name = "getItem")]
+    fn get_item_of(&self, manufacturer: Address, unique_id: String) -> Result<ItemTuple, EriError> {
+        self.get_item_by_key(self.qualified_key_of(manufacturer, unique_id)?)
+    }
+
+    fn get_item_by_key(&self, key: FixedBytes<32>) -> Result<ItemTuple, EriError> {
+        self.is_authenticity_set()?;
+
+        // a decommissioned item is still returned, with a zero owner, like verifyOwnership does
+        if self.item_owner(key).is_zero() && !self.items.getter(key).decommissioned.get() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        let item = self.items.getter(key);
+
+        let mut meta = Vec::new();
+
+        for i in 0..item.metadata.len() {
+            meta.push(item.metadata.get(i).unwrap().get_string())
+        }
+
+        Ok(item_tuple(&item, meta))
+    }
+
+    // chain of custody, oldest owner first
+    fn get_item_history(
+        &self,
+        item_id: String,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ProvenanceTuple>, EriError> {
+        Ok(self.history_of(self.key_of(item_id)?, offset, limit))
+    }
+
+    #[selector(name = "getItemHistory")]
+    fn get_item_history_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ProvenanceTuple>, EriError> {
+        Ok(self.history_of(
+            self.qualified_key_of(manufacturer, unique_id)?,
+            offset,
+            limit,
+        ))
+    }
+
+    fn verify_ownership(&self, item_id: String) -> Result<OwnershipTuple, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.ownership_of(self.key_of(item_id)?))
+    }
+
+    #[selector(name = "verifyOwnership")]
+    fn verify_ownership_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<OwnershipTuple, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.ownership_of(self.qualified_key_of(manufacturer, unique_id)?))
+    }
+
+    fn token_id_of(&self, item_id: String) -> Result<U256, EriError> {
+        Ok(token_id(self.key_of(item_id)?))
+    }
+
+    #[selector(name = "tokenIdOf")]
+    fn qualified_token_id_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<U256, EriError> {
+        Ok(token_id(self.qualified_key_of(manufacturer, unique_id)?))
+    }
+
+    // the key an item is stored under, also its ERC-721 token id
+    fn item_key_of(&self, manufacturer: Address, unique_id: String) -> FixedBytes<32> {
+        item_key(manufacturer, &unique_id)
+    }
+
+    fn royalty_info(&self, token_id: U256, sale_price: U256) -> Result<(Address, U256), EriError> {
+        self.is_authenticity_set()?;
+
+        self.owner_of(token_id)?;
+
+        let manufacturer = self
+            .items
+            .getter(token_key(token_id))
+            .manufacturer_address
+            .get();
+
+        self.royalty_for(manufacturer, sale_price)
+    }
+
+    fn name(&self) -> String {
+        String::from(TOKEN_NAME)
+    }
+
+    fn symbol(&self) -> String {
+        String::from(TOKEN_SYMBOL)
+    }
+
+    #[selector(name = "tokenURI")]
+    fn token_uri(&self, token_id: U256) -> Result<String, EriError> {
+        self.owner_of(token_id)?;
+
+        Ok(format!("{}{}", self.base_uri.get_string(), token_id))
+    }
+
+    fn balance_of(&self, owner: Address) -> Result<U256, EriError> {
+        self.address_zero_check(owner)?;
+
+        Ok(self.balances.get(owner))
+    }
+
+    fn owner_of(&self, token_id: U256) -> Result<Address, EriError> {
+        let owner = self.item_owner(token_key(token_id));
+
+        if owner.is_zero() {
+            return Err(DoesNotExist(DOES_NOT_EXIST {}));
+        }
+
+        Ok(owner)
+    }
+
+    fn transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), EriError> {
+        self.transfer_token(from, to, token_id)
+    }
+
+    fn safe_transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        token_id: U256,
+    ) -> Result<(), EriError> {
+        self.safe_transfer_from_with_data(from, to, token_id, Bytes(Vec::new()))
+    }
+
+    #[selector(name = "safeTransferFrom")]
+    fn safe_transfer_from_with_data(
+        &mut self,
+        from: Address,
+        to: Address,
+        token_id: U256,
+        data: Bytes,
+    ) -> Result<(), EriError> {
+        self.transfer_token(from, to, token_id)?;
+
+        if self.vm().code_size(to) == 0 {
+            return Ok(());
+        }
+
+        self.lock()?;
+
+        let received = check_on_erc721_received(
+            self.vm(),
+            self.vm().msg_sender(),
+            from,
+            to,
+            token_id,
+            data.0,
+        );
+
+        self.unlock();
+
+        received
+    }
+
+    fn approve(&mut self, approved: Address, token_id: U256) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+        let key = token_key(token_id);
+        let owner = self.owner_of(token_id)?;
+
+        // an approved address cannot pass its approval on, only the owner and operators can
+        if self.is_recovered(owner)
+            || (!self.acts_for(owner, caller) && !self.operator_approvals.getter(owner).get(caller))
+        {
+            return Err(OnlyOwner(ONLY_OWNER { owner: caller }));
+        }
+
+        if approved == owner {
+            return Err(InvalidRecipient(INVALID_RECIPIENT { to: approved }));
+        }
+
+        self.token_approvals.setter(key).set(approved);
+
+        log(
+            self.vm(),
+            Approval {
+                owner,
+                approved,
+                tokenId: token_id,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn get_approved(&self, token_id: U256) -> Result<Address, EriError> {
+        self.owner_of(token_id)?;
+
+        Ok(self.token_approvals.get(token_key(token_id)))
+    }
+
+    fn set_approval_for_all(&mut self, operator: Address, approved: bool) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.address_zero_check(operator)?;
+
+        if operator == caller {
+            return Err(InvalidRecipient(INVALID_RECIPIENT { to: operator }));
+        }
+
+        // the count lets deregister tell whether any approval is still standing
+        if self.operator_approvals.getter(caller).get(operator) != approved {
+            let count = self.operator_counts.get(caller);
+
+            self.operator_counts.setter(caller).set(if approved {
+                count + U256::from(1)
+            } else {
+                count - U256::from(1)
+            });
+        }
+
+        self.operator_approvals
+            .setter(caller)
+            .setter(operator)
+            .set(approved);
+
+        log(
+            self.vm(),
+            ApprovalForAll {
+                owner: caller,
+                operator,
+                approved,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn is_approved_for_all(&self, owner: Address, operator: Address) -> bool {
+        self.operator_approvals.getter(owner).get(operator)
+    }
+
+    // hands temporary custody to a renter or repair shop, ownership is left untouched
+    fn set_user(&mut self, token_id: U256, user: Address, expires: u64) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = token_key(token_id);
+        self.is_owner_or_approved(key)?;
+
+        let mut item_user = self.item_users.setter(key);
+        item_user.user.set(user);
+        item_user.expires.set(U256::from(expires));
+
+        log(
+            self.vm(),
+            UpdateUser {
+                tokenId: token_id,
+                user,
+                expires,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn user_of(&self, token_id: U256) -> Result<Address, EriError> {
+        self.owner_of(token_id)?;
+
+        Ok(self.current_user(token_key(token_id)))
+    }
+
+    fn user_expires(&self, token_id: U256) -> Result<U256, EriError> {
+        self.owner_of(token_id)?;
+
+        Ok(self.item_users.get(token_key(token_id)).expires.get())
+    }
+
+    // a zero start dates the warranty from when the item was first claimed
+    fn set_warranty(
+        &mut self,
+        item_id: String,
+        start: U256,
+        duration: U256,
+        terms_uri: String,
+        transferable: bool,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.key_of(item_id)?;
+
+        self.store_warranty(key, start, duration, terms_uri, transferable)
+    }
+
+    #[selector(name = "setWarranty")]
+    fn set_warranty_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        start: U256,
+        duration: U256,
+        terms_uri: String,
+        transferable: bool,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.store_warranty(key, start, duration, terms_uri, transferable)
+    }
+
+    fn get_warranty(&self, item_id: String) -> Result<WarrantyTuple, EriError> {
+        self.warranty_of(self.key_of(item_id)?)
+    }
+
+    #[selector(name = "getWarranty")]
+    fn get_warranty_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<WarrantyTuple, EriError> {
+        self.warranty_of(self.qualified_key_of(manufacturer, unique_id)?)
+    }
+
+    // service centres are authorised per manufacturer and may log work on any of its items
+    fn set_service_centre(&mut self, centre: Address, authorised: bool) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(centre)?;
+
+        self.service_centres
+            .setter(caller)
+            .setter(centre)
+            .set(authorised);
+
+        log(
+            self.vm(),
+            ServiceCentreSet {
+                manufacturer: caller,
+                centre,
+                authorised,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn is_service_centre(&self, manufacturer: Address, centre: Address) -> bool {
+        self.service_centres.getter(manufacturer).get(centre)
+    }
+
+    // records are append-only, nothing can amend or remove them afterwards
+    fn add_service_record(
+        &mut self,
+        item_id: String,
+        service_type: String,
+        description_hash: FixedBytes<32>,
+        uri: String,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.key_of(item_id)?;
+
+        self.record_service(key, service_type, description_hash, uri)
+    }
+
+    #[selector(name = "addServiceRecord")]
+    fn add_service_record_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        service_type: String,
+        description_hash: FixedBytes<32>,
+        uri: String,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.record_service(key, service_type, description_hash, uri)
+    }
+
+    fn get_service_records(
+        &self,
+        item_id: String,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ServiceTuple>, EriError> {
+        Ok(self.service_records_of(self.key_of(item_id)?, offset, limit))
+    }
+
+    #[selector(name = "getServiceRecords")]
+    fn get_service_records_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+        offset: U256,
+        limit: U256,
+    ) -> Result<Vec<ServiceTuple>, EriError> {
+        Ok(self.service_records_of(
+            self.qualified_key_of(manufacturer, unique_id)?,
+            offset,
+            limit,
+        ))
+    }
+
+    fn get_service_record_count(&self, item_id: String) -> Result<U256, EriError> {
+        let key = self.key_of(item_id)?;
+
+        Ok(U256::from(self.service_records.get(key).len()))
+    }
+
+    #[selector(name = "getServiceRecordCount")]
+    fn get_service_record_count_of(
+        &self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<U256, EriError> {
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        Ok(U256::from(self.service_records.get(key).len()))
+    }
+
+    fn set_item_locked(&mut self, item_id: String, locked: bool) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.key_of(item_id)?;
+
+        self.set_locked(key, locked)
+    }
+
+    #[selector(name = "setItemLocked")]
+    fn set_item_locked_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        locked: bool,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.set_locked(key, locked)
+    }
+
+    // locks every item of a model (the item name) made by the calling manufacturer,
+    // including ones claimed later. The change is announced once through ModelLockSet
+    // rather than with a Locked or Unlocked event per token
+    fn set_model_locked(&mut self, model: String, locked: bool) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(caller)?;
+
+        self.locked_models
+            .setter(caller)
+            .setter(model.clone())
+            .set(locked);
+
+        log(
+            self.vm(),
+            ModelLockSet {
+                manufacturer: caller,
+                model,
+                locked,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn locked(&self, token_id: U256) -> Result<bool, EriError> {
+        self.owner_of(token_id)?;
+
+        Ok(self.is_locked(token_key(token_id)))
+    }
+
+    fn supports_interface(&self, interface_id: FixedBytes<4>) -> bool {
+        erc721::supports_interface(interface_id)
+    }
+
+    fn is_owner(&self, user: Address, item_id: String) -> Result<bool, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.item_owner(self.key_of(item_id)?) == user)
+    }
+
+    #[selector(name = "isOwner")]
+    fn is_owner_of(
+        &self,
+        user: Address,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<bool, EriError> {
+        self.is_authenticity_set()?;
+
+        Ok(self.item_owner(self.qualified_key_of(manufacturer, unique_id)?) == user)
+    }
+
+    // asks the manufacturer to take the item back; it lapses if the item changes hands
+    fn request_decommission(&mut self, item_id: String, requested: bool) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.key_of(item_id)?;
+
+        self.set_decommission_request(key, requested)
+    }
+
+    #[selector(name = "requestDecommission")]
+    fn request_decommission_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        requested: bool,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.set_decommission_request(key, requested)
+    }
+
+    fn is_decommission_requested(&self, token_id: U256) -> bool {
+        self.decommission_requests.get(token_key(token_id))
+    }
+
+    // retires a destroyed or recycled item for good, by its owner or, once the owner has
+    // requested it, by its manufacturer
+    fn decommission_item(&mut self, item_id: String, reason: String) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.key_of(item_id)?;
+
+        self.decommission(key, reason)
+    }
+
+    #[selector(name = "decommissionItem")]
+    fn decommission_item_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+        reason: String,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.decommission(key, reason)
+    }
+
+    // a stolen item cannot be handed on until its owner clears the flag
+    fn report_stolen(&mut self, item_id: String) -> Result<(), EriError> {
+        let key = self.key_of(item_id)?;
+
+        self.set_stolen(key, true)
+    }
+
+    #[selector(name = "reportStolen")]
+    fn report_stolen_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<(), EriError> {
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.set_stolen(key, true)
+    }
+
+    fn clear_stolen(&mut self, item_id: String) -> Result<(), EriError> {
+        let key = self.key_of(item_id)?;
+
+        self.set_stolen(key, false)
+    }
+
+    #[selector(name = "clearStolen")]
+    fn clear_stolen_of(
+        &mut self,
+        manufacturer: Address,
+        unique_id: String,
+    ) -> Result<(), EriError> {
+        let key = self.qualified_key_of(manufacturer, unique_id)?;
+
+        self.set_stolen(key, false)
+    }
+
+    // replaces the caller's guardians; any recovery in flight is dropped
+    fn set_guardians(&mut self, guardians: Vec<Address>, threshold: U256) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        if threshold.is_zero() || threshold > U256::from(guardians.len()) {
+            return Err(InvalidThreshold(INVALID_THRESHOLD { threshold }));
+        }
+
+        for (i, guardian) in guardians.iter().enumerate() {
+            if guardian.is_zero() || *guardian == caller || guardians[..i].contains(guardian) {
+                return Err(InvalidGuardian(INVALID_GUARDIAN {
+                    guardian: *guardian,
+                }));
+            }
+        }
+
+        self.clear_guardians(caller);
+        self.reset_recovery(caller);
+
+        for guardian in guardians {
+            self.guardians.setter(caller).push(guardian);
+            self.is_guardian.setter(caller).setter(guardian).set(true);
+        }
+
+        self.guardian_thresholds.setter(caller).set(threshold);
+
+        log(
+            self.vm(),
+            GuardiansSet {
+                account: caller,
+                threshold,
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // (guardians, threshold)
+    fn get_guardians(&self, account: Address) -> (Vec<Address>, U256) {
+        let list = self.guardians.get(account);
+
+        let mut guardians = Vec::new();
+
+        for i in 0..list.len() {
+            guardians.push(list.get(i).unwrap());
+        }
+
+        (guardians, self.guardian_thresholds.get(account))
+    }
+
+    // guardians each back one address and are tallied per address, so a guardian naming a
+    // different one only moves their own vote. The first address to reach the threshold
+    // starts the timelock, after which only the account or new guardians can reset it
+    fn approve_recovery(&mut self, account: Address, new_address: Address) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.address_zero_check(new_address)?;
+
+        if !self.is_guardian.getter(account).get(caller) {
+            return Err(NotGuardian(NOT_GUARDIAN { caller }));
+        }
+
+        if new_address == account || self.is_registered(new_address).is_ok() {
+            return Err(InvalidRecipient(INVALID_RECIPIENT { to: new_address }));
+        }
+
+        let pending = self.recoveries.get(account).new_address.get();
+
+        if !pending.is_zero() && pending != new_address {
+            return Err(RecoveryPending(RECOVERY_PENDING {
+                newAddress: pending,
+            }));
+        }
+
+        // votes remember the nonce they were cast under, offset by one so zero means none
+        let nonce = self.recoveries.get(account).nonce.get();
+        let stamp = nonce + U256::from(1);
+        let ballots = self.recovery_approvals.getter(account);
+        let (backed, cast) = (
+            ballots.getter(caller).new_address.get(),
+            ballots.getter(caller).nonce.get(),
+        );
+
+        if cast == stamp {
+            if backed == new_address {
+                return Err(AlreadyApproved(ALREADY_APPROVED { guardian: caller }));
+            }
+
+            let tally = self
+                .recovery_tallies
+                .getter(account)
+                .getter(nonce)
+                .get(backed);
+            self.recovery_tallies
+                .setter(account)
+                .setter(nonce)
+                .setter(backed)
+                .set(tally - U256::from(1));
+        } else {
+            let votes = self.recoveries.get(account).votes.get();
+            self.recoveries
+                .setter(account)
+                .votes
+                .set(votes + U256::from(1));
+        }
+
+        let mut ballots = self.recovery_approvals.setter(account);
+        let mut vote = ballots.setter(caller);
+        vote.new_address.set(new_address);
+        vote.nonce.set(stamp);
+
+        let approvals = self
+            .recovery_tallies
+            .getter(account)
+            .getter(nonce)
+            .get(new_address)
+            + U256::from(1);
+        self.recovery_tallies
+            .setter(account)
+            .setter(nonce)
+            .setter(new_address)
+            .set(approvals);
+
+        log(
+            self.vm(),
+            RecoveryApproved {
+                account,
+                guardian: caller,
+                newAddress: new_address,
+            },
+        );
+
+        if !pending.is_zero() {
+            self.recoveries.setter(account).approvals.set(approvals);
+        } else if approvals == self.guardian_thresholds.get(account) {
+            let ready_at = U256::from(self.vm().block_timestamp()) + U256::from(RECOVERY_DELAY);
+
+            let mut recovery = self.recoveries.setter(account);
+            recovery.new_address.set(new_address);
+            recovery.approvals.set(approvals);
+            recovery.ready_at.set(ready_at);
+
+            log(
+                self.vm(),
+                RecoveryStarted {
+                    account,
+                    newAddress: new_address,
+                    readyAt: ready_at,
+                },
+            );
+        }
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // drops the started recovery and every vote cast in the current round
+    fn cancel_recovery(&mut self) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        if self.recoveries.get(caller).votes.get().is_zero() {
+            return Err(NoRecoveryPending(NO_RECOVERY_PENDING { account: caller }));
+        }
+
+        self.reset_recovery(caller);
+
+        log(self.vm(), RecoveryCancelled { account: caller });
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // (new address, approvals, ready at); the address stays zero until one reaches the threshold
+    fn get_recovery(&self, account: Address) -> (Address, U256, U256) {
+        let recovery = self.recoveries.get(account);
+
+        (
+            recovery.new_address.get(),
+            recovery.approvals.get(),
+            recovery.ready_at.get(),
+        )
+    }
+
+    // guardians backing `new_address` in the current round
+    fn get_recovery_approvals(&self, account: Address, new_address: Address) -> U256 {
+        let nonce = self.recoveries.get(account).nonce.get();
+
+        self.recovery_tallies
+            .getter(account)
+            .getter(nonce)
+            .get(new_address)
+    }
+
+    // moves the profile, guardians and ETH proceeds of `account` to the approved address
+    fn execute_recovery(&mut self, account: Address) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        let new_address = self.recoveries.get(account).new_address.get();
+        let ready_at = self.recoveries.get(account).ready_at.get();
+
+        if new_address.is_zero() {
+            return Err(NoRecoveryPending(NO_RECOVERY_PENDING { account }));
+        }
+
+        if ready_at.is_zero() || U256::from(self.vm().block_timestamp()) < ready_at {
+            return Err(RecoveryNotReady(RECOVERY_NOT_READY { readyAt: ready_at }));
+        }
+
+        // the new address may have registered while the timelock ran
+        if self.is_registered(new_address).is_ok() {
+            return Err(InvalidRecipient(INVALID_RECIPIENT { to: new_address }));
+        }
+
+        self.reset_recovery(account);
+
+        let username = self.usernames.get(account).get_string();
+
+        self.users
+            .setter(username.clone())
+            .user_address
+            .set(new_address);
+        self.usernames.setter(new_address).set_str(username);
+        self.usernames.delete(account);
+
+        let (guardians, threshold) = self.get_guardians(account);
+        self.clear_guardians(account);
+
+        for guardian in guardians {
+            self.guardians.setter(new_address).push(guardian);
+            self.is_guardian
+                .setter(new_address)
+                .setter(guardian)
+                .set(true);
+        }
+
+        self.guardian_thresholds.setter(new_address).set(threshold);
+
+        // roles go with the person, otherwise the lost key would keep its admin rights
+        self.move_memberships(account, new_address);
+
+        // so does the inheritance. A recovery proves the person is still around, so a claim
+        // in flight is dropped and items not yet handed to an heir follow the recovery instead
+        let (beneficiary, inactivity_period) = (
+            self.inheritances.get(account).beneficiary.get(),
+            self.inheritances.get(account).inactivity_period.get(),
+        );
+        self.inheritances.delete(account);
+        self.inherited_by.delete(account);
+        self.last_active.delete(account);
+
+        if !beneficiary.is_zero() {
+            let mut inheritance = self.inheritances.setter(new_address);
+            inheritance.beneficiary.set(beneficiary);
+            inheritance.inactivity_period.set(inactivity_period);
+        }
+
+        let now = U256::from(self.vm().block_timestamp());
+        self.last_active.setter(new_address).set(now);
+
+        // from here the old key is powerless. ETH proceeds move now, token proceeds and the
+        // items follow in bounded batches through the migrate calls
+        self.recovered_to.setter(account).set(new_address);
+        self.migrate_proceeds(account, Address::ZERO);
+
+        log(
+            self.vm(),
+            AccountRecovered {
+                oldAddress: account,
+                newAddress: new_address,
+                itemCount: self.balances.get(account),
+            },
+        );
+
+        Ok(())
+    }
+
+    // moves at most `limit` of a recovered account's items to its new address and returns
+    // how many moved; anyone may pay for the gas
+    fn migrate_recovered_items(&mut self, account: Address, limit: U256) -> Result<U256, EriError> {
+        self.is_authenticity_set()?;
+
+        if !self.is_recovered(account) {
+            return Err(NoRecoveryPending(NO_RECOVERY_PENDING { account }));
+        }
+
+        let to = self.recovered_account(account);
+        // the same person keeps even their soulbound items
+        let moved = self.move_items(
+            account,
+            to,
+            PROVENANCE_RECOVERY,
+            limit.saturating_to::<usize>(),
+            |_, _| false,
+        );
+
+        Ok(U256::from(moved))
+    }
+
+    // proceeds are kept per token, so each token a recovered account was paid in moves on its own
+    fn migrate_recovered_proceeds(
+        &mut self,
+        account: Address,
+        token: Address,
+    ) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+
+        if !self.is_recovered(account) {
+            return Err(NoRecoveryPending(NO_RECOVERY_PENDING { account }));
+        }
+
+        if self.migrate_proceeds(account, token).is_zero() {
+            return Err(NothingToWithdraw(NOTHING_TO_WITHDRAW {}));
+        }
+
+        Ok(())
+    }
+
+    // a zero beneficiary removes the arrangement
+    fn set_beneficiary(
+        &mut self,
+        beneficiary: Address,
+        inactivity_period: U256,
+    ) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_registered(caller)?;
+
+        self.touch();
+
+        if beneficiary.is_zero() {
+            self.inheritances.delete(caller);
+        } else {
+            self.is_registered(beneficiary)?;
+
+            if beneficiary == caller {
+                return Err(InvalidRecipient(INVALID_RECIPIENT { to: beneficiary }));
+            }
+
+            if inactivity_period.is_zero() {
+                return Err(InvalidDuration(INVALID_DURATION {}));
+            }
+
+            let mut inheritance = self.inheritances.setter(caller);
+            inheritance.beneficiary.set(beneficiary);
+            inheritance.inactivity_period.set(inactivity_period);
+            inheritance.claim_started_at.set(U256::ZERO);
+        }
+
+        log(
+            self.vm(),
+            BeneficiarySet {
+                account: caller,
+                beneficiary,
+                inactivityPeriod: inactivity_period,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn check_in(&mut self) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_registered(caller)?;
+        self.touch();
+
+        Ok(())
+    }
+
+    // (beneficiary, inactivity period, last active, claim started at)
+    fn get_inheritance(&self, account: Address) -> (Address, U256, U256, U256) {
+        let inheritance = self.inheritances.get(account);
+
+        (
+            inheritance.beneficiary.get(),
+            inheritance.inactivity_period.get(),
+            self.last_active.get(account),
+            inheritance.claim_started_at.get(),
+        )
+    }
+
+    fn start_inheritance_claim(&mut self, account: Address) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        self.is_authenticity_set()?;
+        self.is_not_recovered(account)?;
+
+        let inheritance = self.inheritances.get(account);
+
+        if inheritance.beneficiary.get().is_zero() || caller != inheritance.beneficiary.get() {
+            return Err(NotBeneficiary(NOT_BENEFICIARY { caller }));
+        }
+
+        let now = U256::from(self.vm().block_timestamp());
+        let inactive_from = self.last_active.get(account) + inheritance.inactivity_period.get();
+
+        if now < inactive_from {
+            return Err(OwnerStillActive(OWNER_STILL_ACTIVE {
+                inactiveFrom: inactive_from,
+            }));
+        }
+
+        self.inheritances.setter(account).claim_started_at.set(now);
+
+        log(
+            self.vm(),
+            InheritanceClaimStarted {
+                account,
+                beneficiary: caller,
+                completesAt: now + U256::from(INHERITANCE_CHALLENGE_WINDOW),
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    fn cancel_inheritance_claim(&mut self) -> Result<(), EriError> {
+        let caller = self.vm().msg_sender();
+
+        if self
+            .inheritances
+            .get(caller)
+            .claim_started_at
+            .get()
+            .is_zero()
+        {
+            return Err(NoClaimPending(NO_INHERITANCE_CLAIM { account: caller }));
+        }
+
+        self.touch();
+        self.inheritances
+            .setter(caller)
+            .claim_started_at
+            .set(U256::ZERO);
+
+        log(self.vm(), InheritanceClaimCancelled { account: caller });
+
+        Ok(())
+    }
+
+    fn complete_inheritance_claim(&mut self, account: Address) -> Result<(), EriError> {
+        self.is_authenticity_set()?;
+        self.is_not_recovered(account)?;
+
+        let beneficiary = self.inheritances.get(account).beneficiary.get();
+        let started_at = self.inheritances.get(account).claim_started_at.get();
+
+        // any activity by the owner after the claim started also counts as a cancellation
+        if started_at.is_zero() || self.last_active.get(account) > started_at {
+            return Err(NoClaimPending(NO_INHERITANCE_CLAIM { account }));
+        }
+
+        let completes_at = started_at + U256::from(INHERITANCE_CHALLENGE_WINDOW);
+
+        if U256::from(self.vm().block_timestamp()) < completes_at {
+            return Err(ChallengeWindowOpen(CHALLENGE_WINDOW_OPEN {
+                completesAt: completes_at,
+            }));
+        }
+
+        self.is_registered(beneficiary)?;
+
+        self.inheritances.delete(account);
+
+        // the items follow in bounded batches through migrateInheritedItems, as after a
+        // recovery; the count includes any soulbound items that will stay behind
+        self.inherited_by.setter(account).set(beneficiary);
+
+        log(
+            self.vm(),
+            InheritanceClaimed {
+                account,
+                beneficiary,
+                itemCount: self.balances.get(account),
+            },
+        );
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // moves at most `limit` of an inherited account's items to the beneficiary and returns
+    // how many moved; a batch that comes up short settles the estate
+    fn migrate_inherited_items(&mut self, account: Address, limit: U256) -> Result<U256, EriError> {
+        self.is_authenticity_set()?;
+
+        let beneficiary = self.inherited_by.get(account);
+
+        if beneficiary.is_zero() {
+            return Err(NoClaimPending(NO_INHERITANCE_CLAIM { account }));
+        }
+
+        self.is_registered(beneficiary)?;
+
+        // soulbound items are bound to the person rather than the estate, so locked items,
+        // whether on their own or through their model, stay with the account
+        let moved = U256::from(self.move_items(
+            account,
+            beneficiary,
+            PROVENANCE_INHERITANCE,
+            limit.saturating_to::<usize>(),
+            |contract, key| contract.is_locked(key),
+        ));
+
+        if moved < limit {
+            self.inherited_by.delete(account);
+        }
+
+        Ok(moved)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::royalty::IAuthenticity;
+    use crate::token::IERC20;
+    use alloc::string::ToString;
+    use alloy_primitives::{address, B256};
+    use alloy_sol_types::{SolCall, SolEvent};
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use stylus_sdk::abi::Router;
+    use stylus_sdk::stylus_core::calls::{
+        errors::Error, CallAccess, MutatingCallContext, StaticCallContext, ValueTransfer,
+    };
+    use stylus_sdk::stylus_core::deploy::DeploymentAccess;
+    use stylus_sdk::testing::*;
+
+    const OWNER: Address = address!("0000000000000000000000000000000000000a11");
+    const AUTHENTICITY: Address = address!("0000000000000000000000000000000000000a22");
+    const ALICE: Address = address!("0000000000000000000000000000000000000001");
+    const BOB: Address = address!("0000000000000000000000000000000000000002");
+    const CAROL: Address = address!("0000000000000000000000000000000000000003");
+
+    fn setup(vm: &TestVM) -> Ownership {
+        init(vm, Ownership::from(vm))
+    }
+
+    fn init(vm: &TestVM, mut contract: Ownership) -> Ownership {
+        vm.set_sender(OWNER);
+        let _ = contract.constructor(OWNER);
+        contract.authenticity.set(AUTHENTICITY);
+
+        for (user, name) in [(ALICE, "alice"), (BOB, "bob"), (CAROL, "carol")] {
+            vm.set_sender(user);
+            let _ = contract.user_registers(name.to_string());
+        }
+
+        vm.set_sender(AUTHENTICITY);
+        let _ = contract.create_item(
+            ALICE,
+            "iPhone 15".to_string(),
+            "IMEI-1".to_string(),
+            "SN-1".to_string(),
+            U256::from(1),
+            OWNER,
+            vec!["128GB".to_string(), "Black".to_string()],
+            "APPLE".to_string(),
+        );
+
+        contract
+    }
+
+    fn ownership_code(vm: &TestVM) -> FixedBytes<32> {
+        let (topics, _) = vm.get_emitted_logs().pop().unwrap();
+        topics[1]
+    }
+
+    // TestVM gives the same answer to every identical static call, so it cannot model a
+    // token balance that moves during a payment. TokenVM answers `balanceOf` on `token` from
+    // a queue instead and hands everything else to the wrapped TestVM.
+    #[derive(Clone)]
+    struct TokenVM {
+        vm: TestVM,
+        token: Address,
+        balances: Rc<RefCell<VecDeque<U256>>>,
+    }
+
+    impl TokenVM {
+        fn new(vm: &TestVM, token: Address) -> Self {
+            vm.set_code(token, vec![1]);
+
+            Self {
+                vm: vm.clone(),
+                token,
+                balances: Rc::new(RefCell::new(VecDeque::new())),
+            }
+        }
+
+        fn queue_balances(&self, balances: &[u64]) {
+            let mut queue = self.balances.borrow_mut();
+            queue.clear();
+            queue.extend(balances.iter().map(|balance| U256::from(*balance)));
+        }
+    }
+
+    macro_rules! delegate {
+        ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+            $(fn $name(&self, $($arg: $ty),*) -> $ret { self.vm.$name($($arg),*) })*
+        };
+    }
+
+    impl Host for TokenVM {}
+
+    impl CallAccess for TokenVM {
+        fn static_call(
+            &self,
+            context: &dyn StaticCallContext,
+            to: Address,
+            data: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            if to == self.token && data.starts_with(&IERC20::balanceOfCall::SELECTOR) {
+                let balance = self.balances.borrow_mut().pop_front().unwrap();
+                return Ok(balance.abi_encode());
+            }
+
+            self.vm.static_call(context, to, data)
+        }
+
+        unsafe fn delegate_call(
+            &self,
+            context: &dyn MutatingCallContext,
+            to: Address,
+            data: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            self.vm.delegate_call(context, to, data)
+        }
+
+        delegate! {
+            call(context: &dyn MutatingCallContext, to: Address, data: &[u8]) -> Result<Vec<u8>, Error>;
+        }
+    }
+
+    impl CryptographyAccess for TokenVM {
+        delegate! { native_keccak256(input: &[u8]) -> B256; }
+    }
+
+    impl CalldataAccess for TokenVM {
+        delegate! {
+            read_args(len: usize) -> Vec<u8>;
+            read_return_data(offset: usize, size: Option<usize>) -> Vec<u8>;
+            return_data_size() -> usize;
+            write_result(data: &[u8]) -> ();
+        }
+    }
+
+    // the contract never deploys, so deployment always fails
+    unsafe impl UnsafeDeploymentAccess for TokenVM {
+        unsafe fn create1(&self, _: *const u8, _: usize, _: *const u8, _: *mut u8, _: *mut usize) {}
+
+        unsafe fn create2(
+            &self,
+            _: *const u8,
+            _: usize,
+            _: *const u8,
+            _: *const u8,
+            _: *mut u8,
+            _: *mut usize,
+        ) {
+        }
+    }
+
+    impl DeploymentAccess for TokenVM {
+        unsafe fn deploy(&self, _: &[u8], _: U256, _: Option<B256>) -> Result<Address, Vec<u8>> {
+            Err(Vec::new())
+        }
+    }
+
+    impl StorageAccess for TokenVM {
+        unsafe fn storage_cache_bytes32(&self, key: U256, value: B256) {
+            self.vm.storage_cache_bytes32(key, value)
+        }
+
+        delegate! {
+            storage_load_bytes32(key: U256) -> B256;
+            flush_cache(clear: bool) -> ();
+        }
+    }
+
+    // calls go through CallAccess above, a raw call reports a revert with no data
+    unsafe impl UnsafeCallAccess for TokenVM {
+        unsafe fn call_contract(
+            &self,
+            _: *const u8,
+            _: *const u8,
+            _: usize,
+            _: *const u8,
+            _: u64,
+            outs_len: &mut usize,
+        ) -> u8 {
+            *outs_len = 0;
+            1
+        }
+
+        unsafe fn static_call_contract(
+            &self,
+            _: *const u8,
+            _: *const u8,
+            _: usize,
+            _: u64,
+            outs_len: &mut usize,
+        ) -> u8 {
+            *outs_len = 0;
+            1
+        }
+
+        unsafe fn delegate_call_contract(
+            &self,
+            _: *const u8,
+            _: *const u8,
+            _: usize,
+            _: u64,
+            outs_len: &mut usize,
+        ) -> u8 {
+            *outs_len = 0;
+            1
+        }
+    }
+
+    impl BlockAccess for TokenVM {
+        delegate! {
+            block_basefee() -> U256;
+            block_coinbase() -> Address;
+            block_number() -> u64;
+            block_timestamp() -> u64;
+            block_gas_limit() -> u64;
+        }
+    }
+
+    impl ChainAccess for TokenVM {
+        delegate! { chain_id() -> u64; }
+    }
+
+    impl AccountAccess for TokenVM {
+        delegate! {
+            balance(account: Address) -> U256;
+            contract_address() -> Address;
+            code(account: Address) -> Vec<u8>;
+            code_size(account: Address) -> usize;
+            code_hash(account: Address) -> B256;
+        }
+    }
+
+    impl MemoryAccess for TokenVM {
+        delegate! { pay_for_memory_grow(pages: u16) -> (); }
+    }
+
+    impl MessageAccess for TokenVM {
+        delegate! {
+            msg_sender() -> Address;
+            msg_reentrant() -> bool;
+            msg_value() -> U256;
+            tx_origin() -> Address;
+        }
+    }
+
+    impl MeteringAccess for TokenVM {
+        delegate! {
+            evm_gas_left() -> u64;
+            evm_ink_left() -> u64;
+            tx_gas_price() -> U256;
+            tx_ink_price() -> u32;
+        }
+    }
+
+    impl LogAccess for TokenVM {
+        delegate! {
+            emit_log(input: &[u8], num_topics: usize) -> ();
+            raw_log(topics: &[B256], data: &[u8]) -> Result<(), &'static str>;
+        }
+    }
+
+    impl ValueTransfer for TokenVM {
+        delegate! { transfer_eth(to: Address, amount: U256) -> Result<(), Vec<u8>>; }
+    }
+
+    #[test]
+    fn test_only_recipient_can_claim_before_expiry() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        let (recipient, creator, _, _, _) = contract
+            .get_pending_transfer("IMEI-1".to_string())
+            .ok()
+            .unwrap();
+        assert_eq!((recipient, creator), (BOB, ALICE));
+
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(NotRecipient(_))
+        ));
+
+        vm.set_block_timestamp(DEFAULT_TRANSFER_DURATION + 1);
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(CodeExpired(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_code_requires_earlier_commitment() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let secret = FixedBytes::from([7u8; 32]);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_open_ownership_code("IMEI-1".to_string(), keccak(secret))
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        let commitment = keccak((code, secret, CAROL).abi_encode_sequence());
+
+        vm.set_block_number(10);
+        vm.set_sender(CAROL);
+        assert!(contract.commit_ownership_claim(code, commitment).is_ok());
+        assert!(matches!(
+            contract.reveal_ownership_claim(code, secret),
+            Err(InvalidCommitment(_))
+        ));
+
+        vm.set_block_number(11);
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.reveal_ownership_claim(code, secret),
+            Err(InvalidCommitment(_))
+        ));
+
+        vm.set_sender(CAROL);
+        assert!(contract.reveal_ownership_claim(code, secret).is_ok());
+        assert!(contract.is_owner(CAROL, "IMEI-1".to_string()).ok().unwrap());
+    }
+
+    #[test]
+    fn test_zero_commitment_is_rejected_before_payment() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let secret = FixedBytes::from([7u8; 32]);
+        let price = U256::from(100);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_open_sale_code("IMEI-1".to_string(), keccak(secret), Address::ZERO, price)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        vm.set_sender(CAROL);
+        vm.set_value(price);
+        assert!(matches!(
+            contract.commit_ownership_claim(code, FixedBytes::ZERO),
+            Err(InvalidCommitment(_))
+        ));
+        assert!(contract
+            .claim_commits
+            .getter(code)
+            .getter(CAROL)
+            .deposit
+            .get()
+            .is_zero());
+
+        let commitment = keccak((code, secret, CAROL).abi_encode_sequence());
+        assert!(contract.commit_ownership_claim(code, commitment).is_ok());
+        assert!(contract.refund_claim_deposit(code).is_ok());
+        assert_eq!(contract.get_proceeds(CAROL), price);
+    }
+
+    #[test]
+    fn test_sale_credits_seller_and_royalty() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let price = U256::from(1_000);
+        let royalty_receiver = Address::repeat_byte(0x42);
+
+        vm.mock_static_call(
+            AUTHENTICITY,
+            IAuthenticity::getRoyaltyCall {
+                manufacturer: OWNER,
+            }
+            .abi_encode(),
+            Ok((royalty_receiver, U256::from(500)).abi_encode_sequence()),
+        );
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_sale_code("IMEI-1".to_string(), BOB, Address::ZERO, price)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        vm.set_sender(BOB);
+        vm.set_value(U256::from(1));
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(IncorrectPayment(_))
+        ));
+
+        vm.set_value(price);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+        assert_eq!(contract.get_proceeds(ALICE), U256::from(950));
+        assert_eq!(contract.get_proceeds(royalty_receiver), U256::from(50));
+        assert!(contract.is_owner(BOB, "IMEI-1".to_string()).ok().unwrap());
+    }
+
+    #[test]
+    fn test_erc20_sale_credits_what_arrives() {
+        const TOKEN: Address = address!("0000000000000000000000000000000000000e20");
+        let price = U256::from(1_000);
+
+        let vm = TestVM::default();
+        let token_vm = TokenVM::new(&vm, TOKEN);
+        let mut contract = init(&vm, Ownership::from(&token_vm));
+
+        vm.mock_static_call(
+            AUTHENTICITY,
+            IAuthenticity::getRoyaltyCall {
+                manufacturer: OWNER,
+            }
+            .abi_encode(),
+            Ok((Address::ZERO, U256::ZERO).abi_encode_sequence()),
+        );
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_sale_code("IMEI-1".to_string(), BOB, TOKEN, price)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        let transfer_from = IERC20::transferFromCall {
+            from: BOB,
+            to: vm.contract_address(),
+            amount: price,
+        }
+        .abi_encode();
+
+        // a token that reports failure instead of reverting
+        vm.mock_call(TOKEN, transfer_from.clone(), Ok(false.abi_encode()));
+        token_vm.queue_balances(&[0]);
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(TokenTransferFailed(_))
+        ));
+
+        // a USDT-style token returning nothing that keeps 1% of every transfer
+        vm.mock_call(TOKEN, transfer_from, Ok(Vec::new()));
+        token_vm.queue_balances(&[0, 990]);
+
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+        assert_eq!(contract.get_token_proceeds(TOKEN, ALICE), U256::from(990));
+        assert!(contract.is_owner(BOB, "IMEI-1".to_string()).ok().unwrap());
+
+        vm.set_sender(ALICE);
+        contract.entered.set(true);
+        assert!(matches!(
+            contract.withdraw_token_proceeds(TOKEN),
+            Err(Reentrancy(_))
+        ));
+
+        contract.entered.set(false);
+        assert!(contract.withdraw_token_proceeds(TOKEN).is_ok());
+        assert!(contract.get_token_proceeds(TOKEN, ALICE).is_zero());
+    }
+
+    #[test]
+    fn test_token_without_code_cannot_pay() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token = Address::repeat_byte(0x20);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_sale_code("IMEI-1".to_string(), BOB, token, U256::from(10))
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        // every call to an empty account succeeds, so it must not count as a payment
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(TokenTransferFailed(_))
+        ));
+        assert!(!contract.entered.get());
+        assert!(contract.is_owner(ALICE, "IMEI-1".to_string()).ok().unwrap());
+    }
+
+    #[test]
+    fn test_erc721_transfer_from_moves_item() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.transfer_from(ALICE, BOB, token_id),
+            Err(OnlyOwner(_))
+        ));
+
+        vm.set_sender(ALICE);
+        assert!(contract.transfer_from(ALICE, BOB, token_id).is_ok());
+        assert_eq!(contract.owner_of(token_id).ok(), Some(BOB));
+        assert_eq!(contract.balance_of(ALICE).ok(), Some(U256::ZERO));
+        assert_eq!(contract.balance_of(BOB).ok(), Some(U256::from(1)));
+    }
+
+    #[test]
+    fn test_every_advertised_interface_is_routed() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        for id in [
+            0x01ffc9a7u32,
+            0x80ac58cd,
+            0x5b5e139f,
+            0x2a55205a,
+            0xb45a3c0e,
+            0xad092b5c,
+        ] {
+            assert!(contract.supports_interface(FixedBytes(id.to_be_bytes())));
+        }
+        assert!(!contract.supports_interface(FixedBytes([0xff; 4])));
+
+        // a wallet that sees an interface advertised will call any of its functions
+        for selectors in erc721::SUPPORTED_INTERFACES {
+            for selector in selectors {
+                let selector = u32::from_be_bytes(*selector);
+
+                assert!(
+                    <Ownership as Router<Ownership>>::route(&mut contract, selector, &[]).is_some()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_manufacturer_locked_item_is_soulbound() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(ALICE);
+        assert!(matches!(
+            contract.set_item_locked("IMEI-1".to_string(), true),
+            Err(Unauthorized(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract.set_item_locked("IMEI-1".to_string(), true).is_ok());
+        assert_eq!(contract.locked(token_id).ok(), Some(true));
+
+        vm.set_sender(ALICE);
+        assert!(matches!(
+            contract.generate_change_of_ownership_code("IMEI-1".to_string(), BOB),
+            Err(ItemLocked(_))
+        ));
+        assert!(matches!(
+            contract.transfer_from(ALICE, BOB, token_id),
+            Err(ItemLocked(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_item_locked("IMEI-1".to_string(), false)
+            .is_ok());
+        assert_eq!(contract.locked(token_id).ok(), Some(false));
+    }
+
+    #[test]
+    fn test_lock_stops_a_code_issued_before_it() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_model_locked("iPhone 15".to_string(), true)
+            .is_ok());
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.new_owner_claim_ownership(code),
+            Err(ItemLocked(_))
+        ));
+        assert_eq!(
+            contract
+                .verify_ownership("IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .3,
+            ALICE
+        );
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_model_locked("iPhone 15".to_string(), false)
+            .is_ok());
+
+        vm.set_sender(BOB);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+    }
+
+    #[test]
+    fn test_item_unlock_under_a_model_lock_emits_nothing() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_model_locked("iPhone 15".to_string(), true)
+            .is_ok());
+        assert!(contract.set_item_locked("IMEI-1".to_string(), true).is_ok());
+        assert!(contract
+            .set_item_locked("IMEI-1".to_string(), false)
+            .is_ok());
+        assert_eq!(contract.locked(token_id).ok(), Some(true));
+
+        let status_events = vm
+            .get_emitted_logs()
+            .iter()
+            .filter(|(topics, _)| {
+                topics[0] == Locked::SIGNATURE_HASH || topics[0] == Unlocked::SIGNATURE_HASH
+            })
+            .count();
+        assert_eq!(status_events, 0);
+
+        assert!(contract
+            .set_model_locked("iPhone 15".to_string(), false)
+            .is_ok());
+        assert_eq!(contract.locked(token_id).ok(), Some(false));
+    }
+
+    #[test]
+    fn test_item_history_records_every_owner() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_block_timestamp(100);
+        vm.set_sender(ALICE);
+        assert!(contract.transfer_from(ALICE, BOB, token_id).is_ok());
+
+        let history = contract
+            .get_item_history("IMEI-1".to_string(), U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].0, ALICE);
+        assert_eq!(history[0].2, PROVENANCE_MINT);
+        assert_eq!(history[1], (BOB, U256::from(100), PROVENANCE_GIFT));
+
+        let page = contract
+            .get_item_history("IMEI-1".to_string(), U256::from(1), U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].0, BOB);
+    }
+
+    #[test]
+    fn test_my_items_stay_dense_after_transfer() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                ALICE,
+                "iPad".to_string(),
+                "IMEI-2".to_string(),
+                "SN-2".to_string(),
+                U256::from(2),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            )
+            .is_ok());
+
+        vm.set_sender(ALICE);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+        assert!(contract.transfer_from(ALICE, BOB, token_id).is_ok());
+
+        let items = contract.get_all_my_items().ok().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].1, "IMEI-2");
+
+        // the moved id must no longer be tracked for the old owner
+        let moved = contract.item_key_of(OWNER, "IMEI-1".to_string());
+        let kept = contract.item_key_of(OWNER, "IMEI-2".to_string());
+        assert!(contract.my_item_indexes.getter(ALICE).get(moved).is_zero());
+        assert_eq!(
+            contract.my_item_indexes.getter(ALICE).get(kept),
+            U256::from(1)
+        );
+    }
+
+    #[test]
+    fn test_transfer_preserves_metadata() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let before = contract.get_item("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        vm.set_sender(BOB);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+
+        let after = contract.get_item("IMEI-1".to_string()).ok().unwrap();
+        assert_eq!(after.4, BOB);
+        assert_eq!(after.6, vec!["128GB".to_string(), "Black".to_string()]);
+        assert_eq!(after.6, before.6);
+    }
+
+    #[test]
+    fn test_items_of_pages_and_filters() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        for (id, date, maker) in [("IMEI-2", 5, OWNER), ("IMEI-3", 9, CAROL)] {
+            assert!(contract
+                .create_item(
+                    ALICE,
+                    "Phone".to_string(),
+                    id.to_string(),
+                    id.to_string(),
+                    U256::from(date),
+                    maker,
+                    Vec::new(),
+                    "MAKER".to_string(),
+                )
+                .is_ok());
+        }
+
+        assert_eq!(contract.item_count_of(ALICE), U256::from(3));
+
+        let page = contract
+            .get_items_of(ALICE, U256::from(1), U256::from(5))
+            .ok()
+            .unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].1, "IMEI-2");
+
+        let by_maker = contract
+            .get_items_of_by_manufacturer(ALICE, CAROL, U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(by_maker.len(), 1);
+        assert_eq!(by_maker[0].1, "IMEI-3");
+
+        let by_date = contract
+            .get_items_of_by_date(
+                ALICE,
+                U256::from(1),
+                U256::from(5),
+                U256::ZERO,
+                U256::from(10),
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(by_date.len(), 2);
+    }
+
+    #[test]
+    fn test_manufacturer_index_tracks_claims_and_transfers() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(ALICE);
+        assert!(contract.transfer_from(ALICE, BOB, token_id).is_ok());
+
+        assert_eq!(
+            contract.get_manufacturer_stats(OWNER),
+            (U256::from(1), U256::from(1))
+        );
+        assert_eq!(
+            contract
+                .get_manufacturer_items(OWNER, U256::ZERO, U256::from(10))
+                .ok(),
+            Some(vec!["IMEI-1".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_item_keys_match_token_ids() {
+        let vm = TestVM::default();
+        let contract = setup(&vm);
+
+        let key = contract.item_key_of(OWNER, "IMEI-1".to_string());
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        assert_eq!(token_id, utility::token_id(key));
+        assert_eq!(token_key(token_id), key);
+        assert_eq!(contract.owner_of(token_id).ok(), Some(ALICE));
+
+        // unknown ids used to fall back to the zero key, i.e. token 0 owned by nobody
+        assert!(matches!(
+            contract.token_id_of("unknown".to_string()),
+            Err(ItemDoesNotExist(_))
+        ));
+        assert!(matches!(
+            contract.is_owner(Address::ZERO, "unknown".to_string()),
+            Err(ItemDoesNotExist(_))
+        ));
+    }
+
+    #[test]
+    fn test_unique_ids_are_scoped_per_manufacturer() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        // another brand reusing the same id no longer collides with the first one
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                BOB,
+                "Galaxy".to_string(),
+                "IMEI-1".to_string(),
+                "SN-9".to_string(),
+                U256::from(3),
+                CAROL,
+                Vec::new(),
+                "SAMSUNG".to_string(),
+            )
+            .is_ok());
+
+        // but the same brand cannot issue it twice
+        assert!(matches!(
+            contract.create_item(
+                BOB,
+                "iPhone 15".to_string(),
+                "IMEI-1".to_string(),
+                "SN-1".to_string(),
+                U256::from(1),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            ),
+            Err(AlreadyClaimed(_))
+        ));
+
+        let apple = contract
+            .verify_ownership_of(OWNER, "IMEI-1".to_string())
+            .ok()
+            .unwrap();
+        let samsung = contract
+            .get_item_of(CAROL, "IMEI-1".to_string())
+            .ok()
+            .unwrap();
+
+        assert_eq!(apple.3, ALICE);
+        assert_eq!(samsung.4, BOB);
+        assert_eq!(samsung.0, "Galaxy");
+    }
+
+    #[test]
+    fn test_second_brand_item_is_managed_by_qualified_id() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                BOB,
+                "Galaxy".to_string(),
+                "IMEI-1".to_string(),
+                "SN-9".to_string(),
+                U256::from(3),
+                CAROL,
+                Vec::new(),
+                "SAMSUNG".to_string(),
+            )
+            .is_ok());
+
+        // the bare id now names two items, so it resolves to neither
+        assert!(matches!(
+            contract.verify_ownership("IMEI-1".to_string()),
+            Err(AmbiguousItemId(_))
+        ));
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.generate_change_of_ownership_code("IMEI-1".to_string(), ALICE),
+            Err(AmbiguousItemId(_))
+        ));
+
+        // transfer
+        assert!(contract
+            .generate_change_of_ownership_code_of(CAROL, "IMEI-1".to_string(), ALICE)
+            .is_ok());
+        let code = ownership_code(&vm);
+        assert_eq!(
+            contract
+                .get_pending_transfer_of(CAROL, "IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .0,
+            ALICE
+        );
+
+        vm.set_sender(ALICE);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+        assert!(contract
+            .is_owner_of(ALICE, CAROL, "IMEI-1".to_string())
+            .ok()
+            .unwrap());
+
+        // stolen
+        assert!(contract
+            .report_stolen_of(CAROL, "IMEI-1".to_string())
+            .is_ok());
+        assert!(
+            contract
+                .verify_ownership_of(CAROL, "IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .4
+        );
+        assert!(
+            !contract
+                .verify_ownership_of(OWNER, "IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .4
+        );
+        assert!(matches!(
+            contract.report_stolen("IMEI-1".to_string()),
+            Err(AmbiguousItemId(_))
+        ));
+
+        // history
+        let history = contract
+            .get_item_history_of(CAROL, "IMEI-1".to_string(), U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].0, history[0].2), (BOB, PROVENANCE_MINT));
+        assert_eq!((history[1].0, history[1].2), (ALICE, PROVENANCE_GIFT));
+
+        let apple_history = contract
+            .get_item_history_of(OWNER, "IMEI-1".to_string(), U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(apple_history.len(), 1);
+        assert_eq!(
+            contract
+                .qualified_token_id_of(CAROL, "IMEI-1".to_string())
+                .ok()
+                .unwrap(),
+            token_id(item_key(CAROL, "IMEI-1"))
+        );
+    }
+
+    #[test]
+    fn test_username_change_and_deregistration() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.change_username("alice".to_string()),
+            Err(NotAvailable(_))
+        ));
+        assert!(contract.change_username("caroline".to_string()).is_ok());
+        assert_eq!(contract.get_user(CAROL).ok().unwrap().1, "caroline");
+
+        // the old name is free again
+        vm.set_sender(OWNER);
+        assert!(contract.user_registers("carol".to_string()).is_ok());
+
+        vm.set_sender(ALICE);
+        assert!(matches!(contract.deregister(), Err(StillOwnsItems(_))));
+
+        vm.set_sender(CAROL);
+        assert!(contract.deregister().is_ok());
+        assert!(matches!(contract.get_user(CAROL), Err(NotExist(_))));
+    }
+
+    #[test]
+    fn test_deregistration_leaves_no_settings_behind() {
+        const NEW_CAROL: Address = address!("0000000000000000000000000000000000000004");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(CAROL);
+        assert!(contract
+            .set_guardians(vec![ALICE, BOB], U256::from(1))
+            .is_ok());
+        assert!(contract.set_beneficiary(BOB, U256::from(100)).is_ok());
+        assert!(contract.set_approval_for_all(ALICE, true).is_ok());
+        assert!(contract.set_approval_for_all(BOB, true).is_ok());
+        assert!(contract.set_approval_for_all(BOB, true).is_ok());
+
+        vm.set_sender(ALICE);
+        assert!(contract.approve_recovery(CAROL, NEW_CAROL).is_ok());
+
+        vm.set_sender(CAROL);
+        assert!(contract.set_approval_for_all(ALICE, false).is_ok());
+        assert!(matches!(contract.deregister(), Err(OperatorsApproved(_))));
+
+        assert!(contract.set_approval_for_all(BOB, false).is_ok());
+        assert!(contract.deregister().is_ok());
+
+        assert_eq!(contract.get_guardians(CAROL), (vec![], U256::ZERO));
+        assert_eq!(contract.get_recovery(CAROL).0, Address::ZERO);
+        assert_eq!(contract.get_inheritance(CAROL).0, Address::ZERO);
+        assert!(contract.last_active.get(CAROL).is_zero());
+    }
+
+    #[test]
+    fn test_guardians_recover_items_after_timelock() {
+        const NEW_ALICE: Address = address!("0000000000000000000000000000000000000004");
+        const TOKEN: Address = address!("0000000000000000000000000000000000000e20");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                ALICE,
+                "iPad".to_string(),
+                "IMEI-2".to_string(),
+                "SN-2".to_string(),
+                U256::from(2),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            )
+            .is_ok());
+
+        // unwithdrawn sale proceeds in ETH and in a token
+        contract.proceeds.setter(ALICE).set(U256::from(5));
+        contract
+            .token_proceeds
+            .setter(TOKEN)
+            .setter(ALICE)
+            .set(U256::from(7));
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .set_guardians(vec![BOB, CAROL], U256::from(2))
+            .is_ok());
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(BOB);
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+        vm.set_sender(CAROL);
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+
+        assert!(matches!(
+            contract.execute_recovery(ALICE),
+            Err(RecoveryNotReady(_))
+        ));
+
+        vm.set_block_timestamp(1_000 + RECOVERY_DELAY);
+        assert!(contract.execute_recovery(ALICE).is_ok());
+        assert_eq!(contract.get_user(NEW_ALICE).ok().unwrap().1, "alice");
+        assert!(matches!(contract.get_user(ALICE), Err(NotExist(_))));
+        assert_eq!(contract.get_proceeds(NEW_ALICE), U256::from(5));
+
+        // the lost key can no longer touch what it left behind
+        vm.set_sender(ALICE);
+        assert!(matches!(
+            contract.report_stolen("IMEI-2".to_string()),
+            Err(OnlyOwner(_))
+        ));
+        assert!(matches!(
+            contract.withdraw_token_proceeds(TOKEN),
+            Err(Unauthorized(_))
+        ));
+        assert!(matches!(
+            contract.user_registers("alice2".to_string()),
+            Err(Unauthorized(_))
+        ));
+
+        // items move in bounded batches, token proceeds one token at a time
+        vm.set_sender(BOB);
+        assert_eq!(
+            contract
+                .migrate_recovered_items(ALICE, U256::from(1))
+                .ok()
+                .unwrap(),
+            U256::from(1)
+        );
+        assert_eq!(contract.balance_of(ALICE).ok().unwrap(), U256::from(1));
+        assert_eq!(
+            contract
+                .migrate_recovered_items(ALICE, U256::from(10))
+                .ok()
+                .unwrap(),
+            U256::from(1)
+        );
+        assert_eq!(contract.balance_of(NEW_ALICE).ok().unwrap(), U256::from(2));
+
+        assert!(contract.migrate_recovered_proceeds(ALICE, TOKEN).is_ok());
+        assert_eq!(contract.get_token_proceeds(TOKEN, NEW_ALICE), U256::from(7));
+        assert!(matches!(
+            contract.migrate_recovered_proceeds(ALICE, TOKEN),
+            Err(NothingToWithdraw(_))
+        ));
+
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+        assert_eq!(contract.owner_of(token_id).ok(), Some(NEW_ALICE));
+
+        let history = contract
+            .get_item_history("IMEI-1".to_string(), U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(history[1].2, PROVENANCE_RECOVERY);
+    }
+
+    #[test]
+    fn test_dissenting_guardian_cannot_reset_approvals() {
+        const NEW_ALICE: Address = address!("0000000000000000000000000000000000000004");
+        const ROGUE: Address = address!("0000000000000000000000000000000000000005");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .set_guardians(vec![BOB, CAROL, OWNER], U256::from(2))
+            .is_ok());
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(BOB);
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+
+        // a second address only draws CAROL's own vote, BOB's approval stands
+        vm.set_sender(CAROL);
+        assert!(contract.approve_recovery(ALICE, ROGUE).is_ok());
+        assert_eq!(
+            contract.get_recovery_approvals(ALICE, NEW_ALICE),
+            U256::from(1)
+        );
+        assert_eq!(contract.get_recovery(ALICE).0, Address::ZERO);
+
+        // switching back moves the vote rather than counting it twice
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+        assert_eq!(contract.get_recovery_approvals(ALICE, ROGUE), U256::ZERO);
+        assert_eq!(contract.get_recovery(ALICE).0, NEW_ALICE);
+
+        // once the timelock runs nobody but the account can redirect it
+        vm.set_sender(OWNER);
+        assert!(matches!(
+            contract.approve_recovery(ALICE, ROGUE),
+            Err(RecoveryPending(_))
+        ));
+
+        vm.set_block_timestamp(1_000 + RECOVERY_DELAY);
+        assert!(contract.execute_recovery(ALICE).is_ok());
+        assert!(contract
+            .migrate_recovered_items(ALICE, U256::from(10))
+            .is_ok());
+        assert!(contract
+            .is_owner(NEW_ALICE, "IMEI-1".to_string())
+            .ok()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_recovery_moves_organisation_roles() {
+        const NEW_CAROL: Address = address!("0000000000000000000000000000000000000004");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let org = organisation_address("acme");
+
+        vm.set_sender(CAROL);
+        assert!(contract.create_organisation("acme".to_string()).is_ok());
+        assert!(contract.set_member_role(org, BOB, ROLE_MEMBER).is_ok());
+
+        // leaving would strand the organisation without an admin
+        assert!(matches!(contract.deregister(), Err(LastAdmin(_))));
+
+        assert!(contract.set_guardians(vec![BOB], U256::from(1)).is_ok());
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(BOB);
+        assert!(contract.approve_recovery(CAROL, NEW_CAROL).is_ok());
+        vm.set_block_timestamp(1_000 + RECOVERY_DELAY);
+        assert!(contract.execute_recovery(CAROL).is_ok());
+
+        assert_eq!(contract.get_member_role(org, NEW_CAROL), ROLE_ADMIN);
+        assert_eq!(contract.get_member_role(org, CAROL), ROLE_NONE);
+
+        let (_, members, _) = contract.get_organisation(org).ok().unwrap();
+        assert_eq!(members.len(), 2);
+        assert!(members.contains(&NEW_CAROL) && !members.contains(&CAROL));
+
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.set_member_role(org, ALICE, ROLE_ADMIN),
+            Err(NotOrgAdmin(_))
+        ));
+
+        // an ordinary member leaving gives up their role
+        vm.set_sender(BOB);
+        assert!(contract.deregister().is_ok());
+        assert_eq!(contract.get_member_role(org, BOB), ROLE_NONE);
+    }
+
+    #[test]
+    fn test_owner_cancels_recovery() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(ALICE);
+        assert!(contract.set_guardians(vec![BOB], U256::from(1)).is_ok());
+
+        vm.set_sender(BOB);
+        assert!(contract.approve_recovery(ALICE, OWNER).is_ok());
+
+        vm.set_sender(ALICE);
+        assert!(contract.cancel_recovery().is_ok());
+
+        vm.set_block_timestamp(RECOVERY_DELAY * 2);
+        assert!(matches!(
+            contract.execute_recovery(ALICE),
+            Err(NoRecoveryPending(_))
+        ));
+    }
+
+    #[test]
+    fn test_stolen_item_blocks_codes_and_shows_in_lookups() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.report_stolen("IMEI-1".to_string()),
+            Err(OnlyOwner(_))
+        ));
+
+        vm.set_sender(ALICE);
+        assert!(contract.report_stolen("IMEI-1".to_string()).is_ok());
+        assert!(
+            contract
+                .verify_ownership("IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .4
+        );
+        assert!(contract.get_item("IMEI-1".to_string()).ok().unwrap().7);
+        assert!(matches!(
+            contract.generate_change_of_ownership_code("IMEI-1".to_string(), BOB),
+            Err(ItemStolen(_))
+        ));
+
+        assert!(contract.clear_stolen("IMEI-1".to_string()).is_ok());
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_decommissioned_item_cannot_be_reclaimed() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.decommission_item("IMEI-1".to_string(), "recycled".to_string()),
+            Err(Unauthorized(_))
+        ));
+
+        // the manufacturer cannot burn a customer's item on its own
+        vm.set_sender(OWNER);
+        assert!(matches!(
+            contract.decommission_item("IMEI-1".to_string(), "recycled".to_string()),
+            Err(Unauthorized(_))
+        ));
+
+        // nor can anyone but the owner ask for a take-back
+        assert!(matches!(
+            contract.request_decommission("IMEI-1".to_string(), true),
+            Err(OnlyOwner(_))
+        ));
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .request_decommission("IMEI-1".to_string(), true)
+            .is_ok());
+        assert!(contract.is_decommission_requested(token_id));
+
+        // once the owner has asked, the manufacturer confirms the take-back
+        vm.set_sender(OWNER);
+        assert!(contract
+            .decommission_item("IMEI-1".to_string(), "recycled".to_string())
+            .is_ok());
+
+        assert!(matches!(contract.owner_of(token_id), Err(DoesNotExist(_))));
+        assert_eq!(contract.item_count_of(ALICE), U256::ZERO);
+        assert_eq!(contract.balance_of(ALICE).ok(), Some(U256::ZERO));
+
+        // the retired record stays readable, showing no owner
+        let item = contract.get_item("IMEI-1".to_string()).ok().unwrap();
+        assert_eq!((item.1.as_str(), item.4), ("IMEI-1", Address::ZERO));
+        let item = contract
+            .get_item_of(OWNER, "IMEI-1".to_string())
+            .ok()
+            .unwrap();
+        assert_eq!(item.4, Address::ZERO);
+        assert_eq!(
+            contract
+                .verify_ownership("IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .3,
+            Address::ZERO
+        );
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(matches!(
+            contract.create_item(
+                BOB,
+                "iPhone 15".to_string(),
+                "IMEI-1".to_string(),
+                "SN-1".to_string(),
+                U256::from(1),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            ),
+            Err(Decommissioned(_))
+        ));
+    }
+
+    #[test]
+    fn test_decommission_request_lapses_on_transfer() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(ALICE);
+        assert!(contract
+            .request_decommission("IMEI-1".to_string(), true)
+            .is_ok());
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+        let code = ownership_code(&vm);
+
+        vm.set_sender(BOB);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+
+        // BOB never agreed to a take-back
+        vm.set_sender(OWNER);
+        assert!(matches!(
+            contract.decommission_item("IMEI-1".to_string(), "recycled".to_string()),
+            Err(Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn test_non_transferable_warranty_lapses_on_resale() {
+        let vm = TestVM::default();
+        vm.set_block_timestamp(10);
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(ALICE);
+        assert!(matches!(
+            contract.set_warranty(
+                "IMEI-1".to_string(),
+                U256::ZERO,
+                U256::from(100),
+                "ipfs://terms".to_string(),
+                false,
+            ),
+            Err(Unauthorized(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_warranty(
+                "IMEI-1".to_string(),
+                U256::ZERO,
+                U256::from(100),
+                "ipfs://terms".to_string(),
+                false,
+            )
+            .is_ok());
+
+        let warranty = contract.get_warranty("IMEI-1".to_string()).ok().unwrap();
+        assert_eq!(warranty.0, U256::from(10));
+        assert_eq!(warranty.1, U256::from(110));
+        assert!(warranty.5);
+
+        vm.set_sender(ALICE);
+        assert!(contract.transfer_from(ALICE, BOB, token_id).is_ok());
+
+        let warranty = contract.get_warranty("IMEI-1".to_string()).ok().unwrap();
+        assert_eq!(warranty.4, ALICE);
+        assert!(!warranty.5);
+    }
+
+    #[test]
+    fn test_authorised_centre_appends_service_records() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.add_service_record(
+                "IMEI-1".to_string(),
+                "battery".to_string(),
+                FixedBytes::ZERO,
+                String::new(),
+            ),
+            Err(NotServiceCentre(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract.set_service_centre(CAROL, true).is_ok());
+
+        vm.set_block_timestamp(50);
+        vm.set_sender(CAROL);
+        for kind in ["battery", "screen"] {
+            assert!(contract
+                .add_service_record(
+                    "IMEI-1".to_string(),
+                    kind.to_string(),
+                    keccak(kind.as_bytes()),
+                    "ipfs://report".to_string(),
+                )
+                .is_ok());
+        }
+
+        assert_eq!(
+            contract.get_service_record_count("IMEI-1".to_string()).ok(),
+            Some(U256::from(2))
+        );
+
+        let page = contract
+            .get_service_records("IMEI-1".to_string(), U256::from(1), U256::from(1))
+            .ok()
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].0, CAROL);
+        assert_eq!(page[0].1, U256::from(50));
+        assert_eq!(page[0].2, "screen");
+    }
+
+    #[test]
+    fn test_approved_operator_lists_for_owner_and_approval_clears() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.generate_change_of_ownership_code("IMEI-1".to_string(), BOB),
+            Err(OnlyOwner(_))
+        ));
+
+        vm.set_sender(ALICE);
+        assert!(contract.approve(CAROL, token_id).is_ok());
+        assert_eq!(contract.get_approved(token_id).ok(), Some(CAROL));
+
+        // the code is still issued in the owner's name
+        vm.set_sender(CAROL);
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), BOB)
+            .is_ok());
+        assert_eq!(
+            contract
+                .get_pending_transfer("IMEI-1".to_string())
+                .ok()
+                .unwrap()
+                .1,
+            ALICE
+        );
+        let code = ownership_code(&vm);
+
+        vm.set_sender(BOB);
+        assert!(contract.new_owner_claim_ownership(code).is_ok());
+        assert_eq!(contract.get_approved(token_id).ok(), Some(Address::ZERO));
+
+        vm.set_sender(BOB);
+        assert!(contract.set_approval_for_all(CAROL, true).is_ok());
+        assert!(contract.is_approved_for_all(BOB, CAROL));
+
+        vm.set_sender(CAROL);
+        assert!(contract.transfer_from(BOB, ALICE, token_id).is_ok());
+        assert_eq!(contract.owner_of(token_id).ok(), Some(ALICE));
+    }
+
+    #[test]
+    fn test_custodian_expires_without_changing_owner() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_block_timestamp(100);
+        vm.set_sender(ALICE);
+        assert!(contract.set_user(token_id, CAROL, 200).is_ok());
+
+        assert_eq!(contract.user_of(token_id).ok(), Some(CAROL));
+        assert_eq!(contract.owner_of(token_id).ok(), Some(ALICE));
+
+        let ownership = contract
+            .verify_ownership("IMEI-1".to_string())
+            .ok()
+            .unwrap();
+        assert_eq!(ownership.3, ALICE);
+        assert_eq!(ownership.5, CAROL);
+
+        vm.set_block_timestamp(200);
+        assert_eq!(contract.user_of(token_id).ok(), Some(Address::ZERO));
+        assert_eq!(contract.user_expires(token_id).ok(), Some(U256::from(200)));
+    }
+
+    #[test]
+    fn test_organisation_admin_manages_shared_items() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let org = organisation_address("acme");
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_sender(BOB);
+        assert!(contract.create_organisation("acme".to_string()).is_ok());
+        assert!(contract.set_member_role(org, CAROL, ROLE_MEMBER).is_ok());
+        assert!(matches!(
+            contract.set_member_role(org, BOB, ROLE_MEMBER),
+            Err(LastAdmin(_))
+        ));
+
+        vm.set_sender(ALICE);
+        assert!(contract.transfer_from(ALICE, org, token_id).is_ok());
+        assert_eq!(contract.item_count_of(org), U256::from(1));
+
+        // members see the organisation's items, outsiders are turned away
+        assert!(matches!(
+            contract.get_organisation_items(org, U256::ZERO, U256::from(10)),
+            Err(NotOrgMember(_))
+        ));
+        vm.set_sender(CAROL);
+        let items = contract
+            .get_organisation_items(org, U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].1, "IMEI-1");
+
+        // plain members cannot move the organisation's items, admins can
+        vm.set_sender(CAROL);
+        assert!(matches!(
+            contract.transfer_from(org, ALICE, token_id),
+            Err(OnlyOwner(_))
+        ));
+
+        vm.set_sender(BOB);
+        assert!(contract
+            .generate_change_of_ownership_code("IMEI-1".to_string(), ALICE)
+            .is_ok());
+
+        let (name, members, roles) = contract.get_organisation(org).ok().unwrap();
+        assert_eq!(name, "acme");
+        assert_eq!(members, vec![BOB, CAROL]);
+        assert_eq!(roles, vec![ROLE_ADMIN, ROLE_MEMBER]);
+    }
+
+    #[test]
+    fn test_beneficiary_inherits_after_inactivity_and_challenge_window() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+        let token_id = contract.token_id_of("IMEI-1".to_string()).ok().unwrap();
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.start_inheritance_claim(ALICE),
+            Err(OwnerStillActive(_))
+        ));
+
+        // the owner turning up during the challenge window cancels the claim
+        vm.set_block_timestamp(1_500);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+        vm.set_sender(ALICE);
+        assert!(contract.cancel_inheritance_claim().is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+        assert!(matches!(
+            contract.complete_inheritance_claim(ALICE),
+            Err(ChallengeWindowOpen(_))
+        ));
+
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        assert!(contract.complete_inheritance_claim(ALICE).is_ok());
+        assert_eq!(contract.owner_of(token_id).ok(), Some(ALICE));
+
+        assert_eq!(
+            contract.migrate_inherited_items(ALICE, U256::from(10)).ok(),
+            Some(U256::from(1))
+        );
+        assert_eq!(contract.owner_of(token_id).ok(), Some(BOB));
+    }
+
+    #[test]
+    fn test_inherited_items_move_in_batches() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        for unique_id in ["IMEI-2", "IMEI-3"] {
+            assert!(contract
+                .create_item(
+                    ALICE,
+                    "iPad".to_string(),
+                    unique_id.to_string(),
+                    unique_id.to_string(),
+                    U256::from(2),
+                    OWNER,
+                    Vec::new(),
+                    "APPLE".to_string(),
+                )
+                .is_ok());
+        }
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.migrate_inherited_items(ALICE, U256::from(2)),
+            Err(NoClaimPending(_))
+        ));
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        assert!(contract.complete_inheritance_claim(ALICE).is_ok());
+
+        assert_eq!(
+            contract.migrate_inherited_items(ALICE, U256::from(2)).ok(),
+            Some(U256::from(2))
+        );
+        assert_eq!(contract.item_count_of(ALICE), U256::from(1));
+
+        // anyone may pay for the next batch, and the short one settles the estate
+        vm.set_sender(CAROL);
+        assert_eq!(
+            contract.migrate_inherited_items(ALICE, U256::from(2)).ok(),
+            Some(U256::from(1))
+        );
+        assert_eq!(contract.item_count_of(BOB), U256::from(3));
+        assert!(matches!(
+            contract.migrate_inherited_items(ALICE, U256::from(2)),
+            Err(NoClaimPending(_))
+        ));
+    }
+
+    #[test]
+    fn test_any_owner_action_interrupts_an_inheritance_claim() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+
+        // reporting a theft is not a transfer, but it still shows the owner is around
+        vm.set_block_timestamp(2_100);
+        vm.set_sender(ALICE);
+        assert!(contract.report_stolen("IMEI-1".to_string()).is_ok());
+
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.complete_inheritance_claim(ALICE),
+            Err(NoClaimPending(_))
+        ));
+    }
+
+    #[test]
+    fn test_recovery_takes_the_inheritance_along_and_drops_the_claim() {
+        const NEW_ALICE: Address = address!("0000000000000000000000000000000000000004");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+        assert!(contract.set_guardians(vec![CAROL], U256::from(1)).is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+
+        vm.set_sender(CAROL);
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+        vm.set_block_timestamp(2_000 + RECOVERY_DELAY);
+        assert!(contract.execute_recovery(ALICE).is_ok());
+
+        // the arrangement now belongs to the new key, which has just shown signs of life
+        assert_eq!(
+            contract.get_inheritance(NEW_ALICE),
+            (
+                BOB,
+                U256::from(500),
+                U256::from(2_000 + RECOVERY_DELAY),
+                U256::ZERO
+            )
+        );
+        assert_eq!(contract.get_inheritance(ALICE).0, Address::ZERO);
+
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        vm.set_sender(BOB);
+        assert!(matches!(
+            contract.complete_inheritance_claim(ALICE),
+            Err(WasRecovered(_))
+        ));
+        assert!(matches!(
+            contract.start_inheritance_claim(ALICE),
+            Err(WasRecovered(_))
+        ));
+        assert!(contract.start_inheritance_claim(NEW_ALICE).is_ok());
+    }
+
+    #[test]
+    fn test_recovery_stops_an_estate_being_handed_over() {
+        const NEW_ALICE: Address = address!("0000000000000000000000000000000000000004");
+
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                ALICE,
+                "iPad".to_string(),
+                "IMEI-2".to_string(),
+                "SN-2".to_string(),
+                U256::from(2),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            )
+            .is_ok());
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+        assert!(contract.set_guardians(vec![CAROL], U256::from(1)).is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        assert!(contract.complete_inheritance_claim(ALICE).is_ok());
+        assert!(contract
+            .migrate_inherited_items(ALICE, U256::from(1))
+            .is_ok());
+
+        vm.set_sender(CAROL);
+        assert!(contract.approve_recovery(ALICE, NEW_ALICE).is_ok());
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW + RECOVERY_DELAY);
+        assert!(contract.execute_recovery(ALICE).is_ok());
+
+        // what the heir already holds stays with them, the rest follows the recovery
+        assert!(matches!(
+            contract.migrate_inherited_items(ALICE, U256::from(1)),
+            Err(NoClaimPending(_))
+        ));
+        assert_eq!(
+            contract.migrate_recovered_items(ALICE, U256::from(1)).ok(),
+            Some(U256::from(1))
+        );
+        assert!(contract.is_owner(BOB, "IMEI-2".to_string()).ok().unwrap());
+        assert!(contract
+            .is_owner(NEW_ALICE, "IMEI-1".to_string())
+            .ok()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_soulbound_items_are_not_inherited() {
+        let vm = TestVM::default();
+        let mut contract = setup(&vm);
+
+        vm.set_sender(AUTHENTICITY);
+        assert!(contract
+            .create_item(
+                ALICE,
+                "iPad".to_string(),
+                "IMEI-2".to_string(),
+                "SN-2".to_string(),
+                U256::from(2),
+                OWNER,
+                Vec::new(),
+                "APPLE".to_string(),
+            )
+            .is_ok());
+
+        vm.set_sender(OWNER);
+        assert!(contract.set_item_locked("IMEI-1".to_string(), true).is_ok());
+
+        vm.set_block_timestamp(1_000);
+        vm.set_sender(ALICE);
+        assert!(contract.set_beneficiary(BOB, U256::from(500)).is_ok());
+
+        vm.set_block_timestamp(2_000);
+        vm.set_sender(BOB);
+        assert!(contract.start_inheritance_claim(ALICE).is_ok());
+        vm.set_block_timestamp(2_000 + INHERITANCE_CHALLENGE_WINDOW);
+        assert!(contract.complete_inheritance_claim(ALICE).is_ok());
+        assert_eq!(
+            contract.migrate_inherited_items(ALICE, U256::from(10)).ok(),
+            Some(U256::from(1))
+        );
+
+        // the locked item stays with the account, the rest passes to the heir
+        assert!(contract.is_owner(ALICE, "IMEI-1".to_string()).ok().unwrap());
+        assert!(contract.is_owner(BOB, "IMEI-2".to_string()).ok().unwrap());
 
-        Ok(self.owned_items.getter(user).getter(item_id).owner.get() == user)
+        let history = contract
+            .get_item_history("IMEI-2".to_string(), U256::ZERO, U256::from(10))
+            .ok()
+            .unwrap();
+        assert_eq!(history[1].2, PROVENANCE_INHERITANCE);
     }
 }
@@ -0,0 +1,30 @@
+// Royalty terms live with the manufacturer records in Authenticity; Ownership only reads them.
+
+use crate::utility::{EriError::*, *};
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::prelude::*;
+use stylus_sdk::stylus_core::calls::context::Call;
+
+sol! {
+    interface IAuthenticity {
+        function getRoyalty(address manufacturer) external view returns (address, uint256);
+    }
+}
+
+/// Returns the manufacturer's royalty receiver and rate in basis points, capped at the whole price.
+pub fn get_royalty(
+    vm: &dyn Host,
+    authenticity: Address,
+    manufacturer: Address,
+) -> Result<(Address, U256), EriError> {
+    let call = IAuthenticity::getRoyaltyCall { manufacturer };
+
+    match vm.static_call(&Call::new(), authenticity, &call.abi_encode()) {
+        Ok(result) => match IAuthenticity::getRoyaltyCall::abi_decode_returns(&result, true) {
+            Ok(terms) => Ok((terms._0, terms._1.min(U256::from(BPS_DENOMINATOR)))),
+            Err(_) => Err(RoyaltyLookupFailed(ROYALTY_LOOKUP_FAILED {})),
+        },
+        Err(_) => Err(RoyaltyLookupFailed(ROYALTY_LOOKUP_FAILED {})),
+    }
+}
@@ -0,0 +1,78 @@
+// ERC-20 helpers tolerant of tokens that do not follow the standard to the letter,
+// e.g. USDT returning nothing from `transfer` or fee-on-transfer tokens.
+
+use crate::utility::{EriError::*, *};
+use alloc::vec::Vec;
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use stylus_sdk::prelude::*;
+use stylus_sdk::stylus_core::calls::context::Call;
+
+sol! {
+    interface IERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+        function balanceOf(address account) external view returns (uint256);
+    }
+}
+
+/// Pulls `amount` of `token` from `from` into this contract and returns how much actually arrived.
+pub fn safe_transfer_from(
+    vm: &dyn Host,
+    token: Address,
+    from: Address,
+    amount: U256,
+) -> Result<U256, EriError> {
+    let this = vm.contract_address();
+    let before = balance_of(vm, token, this)?;
+
+    let call = IERC20::transferFromCall {
+        from,
+        to: this,
+        amount,
+    };
+    call_token(vm, token, call.abi_encode())?;
+
+    let after = balance_of(vm, token, this)?;
+
+    if after <= before {
+        return Err(TokenTransferFailed(TOKEN_TRANSFER_FAILED { token }));
+    }
+
+    Ok(after - before)
+}
+
+pub fn safe_transfer(
+    vm: &dyn Host,
+    token: Address,
+    to: Address,
+    amount: U256,
+) -> Result<(), EriError> {
+    call_token(vm, token, IERC20::transferCall { to, amount }.abi_encode())
+}
+
+fn balance_of(vm: &dyn Host, token: Address, account: Address) -> Result<U256, EriError> {
+    let call = IERC20::balanceOfCall { account };
+
+    match vm.static_call(&Call::new(), token, &call.abi_encode()) {
+        Ok(result) => match U256::abi_decode(&result, false) {
+            Ok(balance) => Ok(balance),
+            Err(_) => Err(TokenTransferFailed(TOKEN_TRANSFER_FAILED { token })),
+        },
+        Err(_) => Err(TokenTransferFailed(TOKEN_TRANSFER_FAILED { token })),
+    }
+}
+
+fn call_token(vm: &dyn Host, token: Address, data: Vec<u8>) -> Result<(), EriError> {
+    // a call to an address without code succeeds silently, so it would look like a payment
+    if vm.code_size(token) == 0 {
+        return Err(TokenTransferFailed(TOKEN_TRANSFER_FAILED { token }));
+    }
+
+    match vm.call(&Call::new(), token, &data) {
+        // tokens that return nothing only signal failure by reverting
+        Ok(result) if result.is_empty() => Ok(()),
+        Ok(result) if bool::abi_decode(&result, false).unwrap_or(false) => Ok(()),
+        _ => Err(TokenTransferFailed(TOKEN_TRANSFER_FAILED { token })),
+    }
+}
@@ -1,12 +1,11 @@
 use alloc::string::String;
 use alloc::vec::Vec;
-use alloy_primitives::bytes::Bytes;
-use alloy_primitives::{Address, U256};
-use alloy_sol_types::sol;
+use alloy_primitives::{Address, FixedBytes, U256};
+use alloy_sol_types::{sol, SolValue};
+use stylus_sdk::crypto::keccak;
 use stylus_sdk::prelude::*;
 use stylus_sdk::storage::{StorageGuard, StorageGuardMut};
 
-
 sol! {
     error ONLY_OWNER(address owner);
     error ADDRESS_ZERO(address zero);
@@ -22,39 +21,142 @@ sol! {
     error NOT_REGISTERED(address user);
     error CANNOT_GENERATE_CODE_FOR_YOURSELF(address caller);
     error ITEM_DOESNT_EXIST(string itemId);
+    error AMBIGUOUS_ITEM_ID(string itemId);
+    error NOT_INTENDED_RECIPIENT(address caller);
+    error OWNERSHIP_CODE_EXPIRED(bytes32 itemHash);
+    error INVALID_DURATION();
+    error INVALID_SECRET();
+    error INVALID_COMMITMENT();
+    error NOT_AN_OPEN_CODE(bytes32 itemHash);
+    error INVALID_PRICE();
+    error INCORRECT_PAYMENT(uint256 expected, uint256 sent);
+    error NOTHING_TO_WITHDRAW();
+    error WITHDRAWAL_FAILED();
+    error TOKEN_TRANSFER_FAILED(address token);
+    error REENTRANT_CALL();
+    error ROYALTY_LOOKUP_FAILED();
+    error INVALID_RECIPIENT(address to);
+    error UNSAFE_RECIPIENT(address to);
+    error ITEM_LOCKED(string itemId);
+    error STILL_OWNS_ITEMS(uint256 count);
+    error INVALID_THRESHOLD(uint256 threshold);
+    error INVALID_GUARDIAN(address guardian);
+    error NOT_GUARDIAN(address caller);
+    error ALREADY_APPROVED(address guardian);
+    error RECOVERY_PENDING(address newAddress);
+    error NO_RECOVERY_PENDING(address account);
+    error RECOVERY_NOT_READY(uint256 readyAt);
+    error ITEM_REPORTED_STOLEN(string itemId);
+    error ITEM_DECOMMISSIONED(string itemId);
+    error NOT_SERVICE_CENTRE(address caller);
+    error NOT_ORGANISATION_ADMIN(address caller);
+    error NOT_ORGANISATION_MEMBER(address caller);
+    error INVALID_ROLE(uint8 role);
+    error LAST_ADMIN();
+    error NOT_BENEFICIARY(address caller);
+    error OWNER_STILL_ACTIVE(uint256 inactiveFrom);
+    error NO_INHERITANCE_CLAIM(address account);
+    error CHALLENGE_WINDOW_OPEN(uint256 completesAt);
+    error OPERATORS_STILL_APPROVED(uint256 count);
+    error ACCOUNT_WAS_RECOVERED(address account, address newAddress);
 
     event ContractCreated(address indexed contractAddress,address indexed owner);
     event UserRegistered(address indexed userAddress, string indexed username);
-    event OwnershipCode(bytes32 indexed ownershipCode,address indexed tempOwner);
+    event UsernameChanged(address indexed userAddress, string indexed oldUsername, string indexed newUsername);
+    event ProfileUpdated(address indexed userAddress);
+    event UserDeregistered(address indexed userAddress);
+    event OwnershipCode(bytes32 indexed ownershipCode,address indexed tempOwner, uint256 expiresAt);
     event ItemCreated(string indexed itemId, address indexed owner);
     event OwnershipClaimed(address indexed newOwner, address indexed oldOwner);
     event CodeRevoked(bytes32 indexed itemHash);
     event AuthenticitySet(address indexed authenticityAddress);
+    event TransferDurationSet(uint256 duration);
+    event ClaimCommitted(bytes32 indexed itemHash, bytes32 commitment);
+    event SaleListed(bytes32 indexed ownershipCode, address indexed token, uint256 price);
+    event ClaimDepositRefunded(bytes32 indexed itemHash, address indexed claimer, uint256 amount);
+    event RoyaltyPaid(address indexed receiver, address indexed token, uint256 amount);
+    event Transfer(address indexed from, address indexed to, uint256 indexed tokenId);
+    event Approval(address indexed owner, address indexed approved, uint256 indexed tokenId);
+    event UpdateUser(uint256 indexed tokenId, address indexed user, uint64 expires);
+    event ApprovalForAll(address indexed owner, address indexed operator, bool approved);
+    event BaseURISet(string baseURI);
+    event Locked(uint256 tokenId);
+    event Unlocked(uint256 tokenId);
+    event ModelLockSet(address indexed manufacturer, string model, bool locked);
+    event ServiceCentreSet(address indexed manufacturer, address indexed centre, bool authorised);
+    event ServiceRecorded(bytes32 indexed itemKey, address indexed centre, string serviceType);
+    event WarrantySet(bytes32 indexed itemKey, uint256 start, uint256 duration, bool transferable);
+    event DecommissionRequested(bytes32 indexed itemKey, address indexed owner, bool requested);
+    event ItemDecommissioned(bytes32 indexed itemKey, address indexed by, string reason);
+    event StolenStatusChanged(bytes32 indexed itemKey, address indexed owner, bool stolen);
+    event OrganisationCreated(address indexed organisation, address indexed creator, string name);
+    event MemberRoleSet(address indexed organisation, address indexed member, uint8 role);
+    event BeneficiarySet(address indexed account, address indexed beneficiary, uint256 inactivityPeriod);
+    event InheritanceClaimStarted(address indexed account, address indexed beneficiary, uint256 completesAt);
+    event InheritanceClaimCancelled(address indexed account);
+    event InheritanceClaimed(address indexed account, address indexed beneficiary, uint256 itemCount);
+    event GuardiansSet(address indexed account, uint256 threshold);
+    event RecoveryApproved(address indexed account, address indexed guardian, address newAddress);
+    event RecoveryStarted(address indexed account, address indexed newAddress, uint256 readyAt);
+    event RecoveryCancelled(address indexed account);
+    event AccountRecovered(address indexed oldAddress, address indexed newAddress, uint256 itemCount);
+    event ProceedsWithdrawn(address indexed account, address indexed token, uint256 amount);
 }
 
-// ItemDoesNotExist(ITEM_DOESNT_EXIST),
-
-#[derive(Debug)]
-pub struct Certificate {
-    pub name: String,
-    pub unique_id: String,
-    pub serial: String,
-    pub date: U256,
-    pub owner: Address,
-    pub metadata_hash: Bytes,
-    pub metadata: Vec<String>,
-}
+/// How long a change of ownership code stays claimable unless the owner configures otherwise (7 days).
+pub const DEFAULT_TRANSFER_DURATION: u64 = 7 * 24 * 60 * 60;
 
-#[derive(Debug)]
-pub struct Item {
-    pub name: String,
-    pub item_id: String,
-    pub serial: String,
-    pub date: U256,
-    pub owner: Address,
-    pub manufacturer: String,
-    pub metadata: Vec<String>,
-}
+pub const TOKEN_NAME: &str = "ERI Authenticated Items";
+pub const TOKEN_SYMBOL: &str = "ERI";
+
+/// How long the old address has to cancel a recovery once guardians approved it (3 days).
+pub const RECOVERY_DELAY: u64 = 3 * 24 * 60 * 60;
+
+/// How long an owner has to cancel a beneficiary's claim once it is started (30 days).
+pub const INHERITANCE_CHALLENGE_WINDOW: u64 = 30 * 24 * 60 * 60;
+
+/// Roles a member can hold in an organisation.
+pub const ROLE_NONE: u8 = 0;
+pub const ROLE_MEMBER: u8 = 1;
+pub const ROLE_ADMIN: u8 = 2;
+
+/// Royalties are expressed in basis points of the sale price.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+pub type ItemTuple = (
+    String,
+    String,
+    String,
+    U256,
+    Address,
+    String,
+    Vec<String>,
+    bool,
+);
+
+/// (name, unique id, owner's username, owner, reported stolen, current custodian)
+pub type OwnershipTuple = (String, String, String, Address, bool, Address);
+
+/// How an owner in an item's provenance came to hold it; the decommissioned entry that
+/// closes a retired item's history has no owner.
+pub const PROVENANCE_MINT: u8 = 0;
+pub const PROVENANCE_GIFT: u8 = 1;
+pub const PROVENANCE_SALE: u8 = 2;
+pub const PROVENANCE_RECOVERY: u8 = 3;
+pub const PROVENANCE_DECOMMISSIONED: u8 = 4;
+pub const PROVENANCE_INHERITANCE: u8 = 5;
+
+/// (recipient, creator, expires at, price, payment token)
+pub type PendingTransferTuple = (Address, Address, U256, U256, Address);
+
+/// (owner, acquired at, kind)
+pub type ProvenanceTuple = (Address, U256, u8);
+
+/// (centre, timestamp, service type, description hash, uri)
+pub type ServiceTuple = (Address, U256, String, FixedBytes<32>, String);
+
+/// (start, expires at, terms uri, transferable, holder, active)
+pub type WarrantyTuple = (U256, U256, String, bool, Address, bool);
 
 #[derive(SolidityError)]
 pub enum EriError {
@@ -72,8 +174,47 @@ pub enum EriError {
     NotClaimed(ITEM_NOT_CLAIMED_YET),
     DoesNotExist(DOES_NOT_EXIST),
     ItemDoesNotExist(ITEM_DOESNT_EXIST),
+    AmbiguousItemId(AMBIGUOUS_ITEM_ID),
+    NotRecipient(NOT_INTENDED_RECIPIENT),
+    CodeExpired(OWNERSHIP_CODE_EXPIRED),
+    InvalidDuration(INVALID_DURATION),
+    InvalidSecret(INVALID_SECRET),
+    InvalidCommitment(INVALID_COMMITMENT),
+    NotOpenCode(NOT_AN_OPEN_CODE),
+    InvalidPrice(INVALID_PRICE),
+    IncorrectPayment(INCORRECT_PAYMENT),
+    NothingToWithdraw(NOTHING_TO_WITHDRAW),
+    WithdrawalFailed(WITHDRAWAL_FAILED),
+    TokenTransferFailed(TOKEN_TRANSFER_FAILED),
+    Reentrancy(REENTRANT_CALL),
+    RoyaltyLookupFailed(ROYALTY_LOOKUP_FAILED),
+    InvalidRecipient(INVALID_RECIPIENT),
+    UnsafeRecipient(UNSAFE_RECIPIENT),
+    ItemLocked(ITEM_LOCKED),
+    StillOwnsItems(STILL_OWNS_ITEMS),
+    InvalidThreshold(INVALID_THRESHOLD),
+    InvalidGuardian(INVALID_GUARDIAN),
+    NotGuardian(NOT_GUARDIAN),
+    AlreadyApproved(ALREADY_APPROVED),
+    RecoveryPending(RECOVERY_PENDING),
+    NoRecoveryPending(NO_RECOVERY_PENDING),
+    RecoveryNotReady(RECOVERY_NOT_READY),
+    ItemStolen(ITEM_REPORTED_STOLEN),
+    Decommissioned(ITEM_DECOMMISSIONED),
+    NotServiceCentre(NOT_SERVICE_CENTRE),
+    NotOrgAdmin(NOT_ORGANISATION_ADMIN),
+    NotOrgMember(NOT_ORGANISATION_MEMBER),
+    InvalidRole(INVALID_ROLE),
+    LastAdmin(LAST_ADMIN),
+    NotBeneficiary(NOT_BENEFICIARY),
+    OwnerStillActive(OWNER_STILL_ACTIVE),
+    NoClaimPending(NO_INHERITANCE_CLAIM),
+    ChallengeWindowOpen(CHALLENGE_WINDOW_OPEN),
+    OperatorsApproved(OPERATORS_STILL_APPROVED),
+    WasRecovered(ACCOUNT_WAS_RECOVERED),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn set_item(
     new_item: &mut StorageGuardMut<crate::Item>,
     user: Address,
@@ -82,6 +223,8 @@ pub fn set_item(
     serial: String,
     date: U256,
     manufacturer_name: String,
+    manufacturer_address: Address,
+    metadata: Vec<String>,
 ) {
     new_item.item_id.set_str(unique_id);
     new_item.owner.set(user);
@@ -89,12 +232,46 @@ pub fn set_item(
     new_item.date.set(date);
     new_item.manufacturer.set_str(manufacturer_name);
     new_item.serial.set_str(serial);
+    new_item.manufacturer_address.set(manufacturer_address);
+
+    for meta in metadata {
+        // Adds a new StorageString slot and returns a guard
+        new_item.metadata.grow().set_str(meta);
+    }
+}
+
+/// Canonical key an item is stored under, namespaced by the manufacturer that issued it.
+pub fn item_key(manufacturer: Address, unique_id: &str) -> FixedBytes<32> {
+    keccak((manufacturer, String::from(unique_id)).abi_encode_sequence())
+}
+
+/// Address an organisation owns items under, derived from its name since nobody holds its key.
+pub fn organisation_address(name: &str) -> Address {
+    let hash = keccak((String::from("organisation"), String::from(name)).abi_encode_sequence());
+
+    Address::from_slice(&hash[12..])
+}
+
+/// Token id an item is known by to NFT tooling, the numeric form of its key.
+pub fn token_id(key: FixedBytes<32>) -> U256 {
+    U256::from_be_bytes(key.0)
+}
+
+pub fn token_key(token_id: U256) -> FixedBytes<32> {
+    FixedBytes::from(token_id.to_be_bytes::<32>())
 }
 
-pub fn item_tuple(
-    item: &StorageGuard<crate::Item>,
-    meta: Vec<String>,
-) -> (String, String, String, U256, Address, String, Vec<String>) {
+/// Clamps an (offset, limit) page request to a list of `len` entries.
+pub fn page_bounds(len: usize, offset: U256, limit: U256) -> (usize, usize) {
+    let start = offset.saturating_to::<usize>().min(len);
+    let end = start
+        .saturating_add(limit.saturating_to::<usize>())
+        .min(len);
+
+    (start, end)
+}
+
+pub fn item_tuple(item: &StorageGuard<crate::Item>, meta: Vec<String>) -> ItemTuple {
     (
         item.name.get_string(),
         item.item_id.get_string(),
@@ -103,5 +280,6 @@ pub fn item_tuple(
         item.owner.get(),
         item.manufacturer.get_string(),
         meta,
+        item.stolen.get(),
     )
-}
\ No newline at end of file
+}